@@ -20,6 +20,14 @@ pub struct Cli {
     #[arg(short, long)]
     pub context: Option<String>,
     
+    /// Number of candidate commit messages to generate and pick from
+    #[arg(short = 'n', long, default_value_t = 1)]
+    pub candidates: usize,
+    
+    /// Configuration profile to use for this invocation
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+    
     /// Extra arguments passed to git commit
     #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
     pub extra_args: Vec<String>,
@@ -44,21 +52,189 @@ pub enum Commands {
         #[command(subcommand)]
         action: CommitlintAction,
     },
+    
+    /// Split the staged changes into several logical commits
+    Split {
+        /// Create the proposed commits without asking
+        #[arg(short, long)]
+        yes: bool,
+        
+        /// Use full GitMoji specification
+        #[arg(long = "fgm")]
+        fgm: bool,
+        
+        /// Additional user input context for the commit messages
+        #[arg(short, long)]
+        context: Option<String>,
+    },
+    
+    /// Generate one commit message for squashing the current branch
+    SquashMessage {
+        /// Branch the current one is going to be merged into, the default branch when left out
+        #[arg(long)]
+        base: Option<String>,
+        
+        /// Squash the branch into a single commit with the generated message
+        #[arg(long)]
+        apply: bool,
+        
+        /// Squash without asking
+        #[arg(short, long)]
+        yes: bool,
+        
+        /// Use full GitMoji specification
+        #[arg(long = "fgm")]
+        fgm: bool,
+        
+        /// Additional user input context for the commit message
+        #[arg(short, long)]
+        context: Option<String>,
+    },
+    
+    /// Generate a pull request title and description for the current branch
+    Pr {
+        /// Branch the pull request is going to be merged into, the default branch when left out
+        #[arg(long)]
+        base: Option<String>,
+        
+        /// Template to fill in when the repository has several in a PULL_REQUEST_TEMPLATE directory
+        #[arg(short, long)]
+        template: Option<String>,
+        
+        /// Write the pull request to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Additional user input context for the pull request
+        #[arg(short, long)]
+        context: Option<String>,
+    },
+    
+    /// Regenerate the messages of existing commits and rewrite them
+    Reword {
+        /// Commits to reword, like `main..` or `HEAD~3..HEAD`; a single revision means the commits after it
+        range: String,
+        
+        /// Rewrite the commits without asking
+        #[arg(short, long)]
+        yes: bool,
+        
+        /// Use full GitMoji specification
+        #[arg(long = "fgm")]
+        fgm: bool,
+        
+        /// Additional user input context for the commit messages
+        #[arg(short, long)]
+        context: Option<String>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
 pub enum ConfigAction {
     /// Get configuration value
     Get {
+        /// Show secrets instead of masking them
+        #[arg(long)]
+        reveal: bool,
+        
         /// Configuration keys to get
         keys: Vec<String>,
     },
     
     /// Set configuration value
     Set {
+        /// Write to the repository's .opencommit.toml
+        #[arg(long, conflicts_with = "global")]
+        local: bool,
+        
+        /// Write to the global ~/.opencommit (default)
+        #[arg(long)]
+        global: bool,
+        
         /// Configuration key-value pairs to set (format: KEY=VALUE)
         key_values: Vec<String>,
     },
+    
+    /// List effective configuration values
+    List {
+        /// Show where each value comes from
+        #[arg(long)]
+        show_origin: bool,
+        
+        /// Show secrets instead of masking them
+        #[arg(long)]
+        reveal: bool,
+    },
+    
+    /// Manage named configuration profiles
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
+    
+    /// Manage secrets stored in the OS keyring or the encrypted secrets file
+    Secret {
+        #[command(subcommand)]
+        action: SecretAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SecretAction {
+    /// Store a secret, read from a prompt or from stdin when it is not a terminal
+    Set {
+        /// Secret name, referenced from config as secret:<name>
+        name: String,
+        
+        /// Use the encrypted secrets file instead of the OS keyring
+        #[arg(long)]
+        file: bool,
+    },
+    
+    /// Delete a stored secret
+    Delete {
+        /// Secret name
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ProfileAction {
+    /// List the defined profiles, marking the active one
+    List,
+    
+    /// Make a profile the default
+    Use {
+        /// Profile name
+        name: String,
+        
+        /// Write to the repository's .opencommit.toml
+        #[arg(long)]
+        local: bool,
+    },
+    
+    /// Create a profile
+    Create {
+        /// Profile name
+        name: String,
+        
+        /// Write to the repository's .opencommit.toml
+        #[arg(long)]
+        local: bool,
+        
+        /// Settings of the profile (format: KEY=VALUE)
+        key_values: Vec<String>,
+    },
+    
+    /// Delete a profile
+    Delete {
+        /// Profile name
+        name: String,
+        
+        /// Delete from the repository's .opencommit.toml
+        #[arg(long)]
+        local: bool,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -1,16 +1,24 @@
 use crate::error::{Error, Result};
-use crate::engine::get_engine;
+use crate::engine::{get_commit_engine, get_commit_engine_with_sampling};
+use crate::engine::engine::{AiEngine, Message, Sampling};
 use crate::prompts::get_main_commit_prompt;
-use crate::utils::git::{assert_git_repo, get_staged_files, get_changed_files, git_add, get_diff};
+use crate::utils::git::{
+    assert_git_repo, get_staged_files, get_changed_files, git_add, get_diff, edit_commit_message,
+    get_unstaged_diff, get_diff_hunks, apply_hunks_to_index, Hunk,
+};
+use crate::commands::commitlint::{enforce_commitlint_rules, load_commitlint_rules};
 use crate::commands::config::Config;
+use crate::utils::spinner::new_spinner;
 
+use std::io::Write;
 use std::process::{Command, Stdio};
+use std::sync::Once;
 use colored::Colorize;
+use git2::Repository;
 use inquire::{Confirm, Select, MultiSelect};
-use indicatif::{ProgressBar, ProgressStyle};
 use log::{info, error, debug};
+use serde_json::Value;
 use tokio::time::sleep;
-use tokio::time::Duration;
 
 // Check message template for placeholder
 fn check_message_template(extra_args: &[String], config: &Config) -> Option<String> {
@@ -29,6 +37,7 @@ pub async fn execute_commit(
     is_stage_all: bool,
     full_gitmoji_spec: bool,
     skip_confirmation: bool,
+    candidates: usize,
 ) -> Result<()> {
     println!("{}", "OpenCommit".bright_blue().bold());
     
@@ -68,30 +77,19 @@ pub async fn execute_commit(
         
         match stage_all {
             Ok(true) => {
-                return execute_commit(extra_args, context, true, full_gitmoji_spec, skip_confirmation).await;
+                return execute_commit(extra_args, context, true, full_gitmoji_spec, skip_confirmation, candidates).await;
             }
             Ok(false) => {
-                // Let user select files to stage
-                let selected_files = MultiSelect::new(
-                    "Select the files you want to add to the commit:",
-                    changed_files.clone()
-                )
-                .prompt();
+                // Let user pick the changes to stage, hunk by hunk
+                let hunks = pick_hunks(&repo, &changed_files)?;
                 
-                match selected_files {
-                    Ok(files) => {
-                        if files.is_empty() {
-                            println!("{}", "No files selected".yellow());
-                            return Err(Error::UserCancelled);
-                        }
-                        
-                        git_add(&repo, &files)?;
-                        staged_files = files;
-                    }
-                    Err(_) => {
-                        return Err(Error::UserCancelled);
-                    }
+                if hunks.is_empty() {
+                    println!("{}", "No changes selected".yellow());
+                    return Err(Error::UserCancelled);
                 }
+                
+                apply_hunks_to_index(&repo, &hunks)?;
+                staged_files = get_staged_files(&repo)?;
             }
             Err(_) => {
                 return Err(Error::UserCancelled);
@@ -112,70 +110,141 @@ pub async fn execute_commit(
     let config = Config::load()?;
     
     // Check if API key is configured
-    if config.api_key.is_none() && config.ai_provider != "ollama" && config.ai_provider != "test" {
-        return Err(Error::NoApiKey);
-    }
+    config.engine_ready()?;
     
     // Generate commit message
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-            .template("{spinner} {msg}")
-            .unwrap(),
-    );
-    spinner.set_message("Generating the commit message");
-    spinner.enable_steady_tick(Duration::from_millis(100));
+    let spinner = new_spinner("Generating the commit message");
     
     let messages = get_main_commit_prompt(
         full_gitmoji_spec,
         context.unwrap_or_default(),
     ).await?;
     
-    let engine = get_engine(&config)?;
-    let mut commit_message = engine.generate_commit_message(messages, &diff).await?;
+    let engine = get_commit_engine(&config)?;
+    let template = check_message_template(&extra_args, &config);
+    let commitlint_rules = load_commitlint_rules(&config).await?;
     
-    // Check for message template
-    if let Some(template) = check_message_template(&extra_args, &config) {
-        let mut new_extra_args = extra_args.clone();
-        let template_index = new_extra_args.iter().position(|arg| arg == &template).unwrap();
-        new_extra_args.remove(template_index);
+    // Every candidate generated this session, rejected ones stay available to pick from
+    let mut history: Vec<String> = Vec::new();
+    
+    if candidates <= 1 {
+        // Swap the spinner for the message as soon as the first tokens arrive
+        let header_shown = Once::new();
+        let on_token = |token: &str| {
+            header_shown.call_once(|| {
+                spinner.finish_and_clear();
+                println!("\n{}", "Generated commit message:".green());
+                println!("{}", "——————————————————".bright_black());
+            });
+            print!("{}", token);
+            let _ = std::io::stdout().flush();
+        };
         
-        commit_message = template.replace(&config.message_template_placeholder, &commit_message);
+        let generated = engine.generate_commit_message_stream(messages.clone(), &diff, &on_token).await;
+        spinner.finish_and_clear();
+        let mut commit_message = generated?;
+        
+        if !commit_message.ends_with('\n') {
+            println!();
+        }
+        println!("{}", "——————————————————".bright_black());
+        
+        // Check the message against the commitlint rules it was generated from
+        if let Some(rules) = &commitlint_rules {
+            let repaired = enforce_commitlint_rules(engine.as_ref(), rules, &messages, &diff, &commit_message).await?;
+            
+            if repaired != commit_message.trim() {
+                println!("\n{}", "Commit message adjusted to follow @commitlint rules:".green());
+                println!("{}", "——————————————————".bright_black());
+                println!("{}", repaired);
+                println!("{}", "——————————————————".bright_black());
+            }
+            
+            commit_message = repaired;
+        }
+        
+        // Check for message template
+        if let Some(template) = &template {
+            commit_message = template.replace(&config.message_template_placeholder, &commit_message);
+            
+            // Display the message once the template is applied
+            println!("\n{}", "Commit message with template:".green());
+            println!("{}", "——————————————————".bright_black());
+            println!("{}", commit_message);
+            println!("{}", "——————————————————".bright_black());
+        }
+        
+        history.push(commit_message);
+    } else {
+        spinner.set_message(format!("Generating {} commit messages", candidates));
+        let generated = generate_candidates(&config, engine.as_ref(), commitlint_rules.as_ref(), &messages, &diff, candidates, template.as_deref()).await;
+        spinner.finish_and_clear();
+        add_candidates(&mut history, generated?);
     }
     
-    spinner.finish_and_clear();
-    
-    // Display generated message
-    println!("\n{}", "Generated commit message:".green());
-    println!("{}", "——————————————————".bright_black());
-    println!("{}", commit_message);
-    println!("{}", "——————————————————".bright_black());
-    
-    // Get confirmation
-    let confirmed = if skip_confirmation {
-        true
-    } else {
-        match Confirm::new("Confirm the commit message?")
-            .with_default(true)
-            .prompt() 
-        {
-            Ok(confirmed) => confirmed,
-            Err(_) => return Err(Error::UserCancelled),
+    // Pick the message to commit, generating more candidates as long as the user asks for them
+    let selected = loop {
+        if skip_confirmation {
+            break Some(history[0].clone());
+        }
+        
+        if history.len() == 1 {
+            let answer = match Select::new("Confirm the commit message?", vec!["Yes", "Edit", "No"]).prompt() {
+                Ok(answer) => answer,
+                Err(_) => return Err(Error::UserCancelled),
+            };
+            
+            match answer {
+                "Yes" => break Some(history[0].clone()),
+                "Edit" => break edit_message(&repo, &history[0])?,
+                _ => {}
+            }
+            
+            // Ask if user wants to regenerate the message
+            let regenerate = match Confirm::new("Do you want to regenerate the message?")
+                .with_default(false)
+                .prompt() 
+            {
+                Ok(regenerate) => regenerate,
+                Err(_) => return Err(Error::UserCancelled),
+            };
+            
+            if !regenerate {
+                break None;
+            }
+        } else {
+            let mut choices = candidate_choices(&history);
+            choices.push(CandidateChoice::Edit);
+            choices.push(CandidateChoice::Regenerate);
+            choices.push(CandidateChoice::Cancel);
+            
+            match Select::new("Pick the commit message:", choices).prompt() {
+                Ok(CandidateChoice::Candidate(_, message)) => break Some(message),
+                Ok(CandidateChoice::Edit) => {
+                    match Select::new("Which message do you want to edit?", candidate_choices(&history)).raw_prompt() {
+                        Ok(choice) => break edit_message(&repo, &history[choice.index])?,
+                        Err(_) => return Err(Error::UserCancelled),
+                    }
+                }
+                Ok(CandidateChoice::Regenerate) => {}
+                Ok(CandidateChoice::Cancel) => break None,
+                Err(_) => return Err(Error::UserCancelled),
+            }
+        }
+        
+        let spinner = new_spinner("Generating new commit messages");
+        
+        let generated = generate_candidates(&config, engine.as_ref(), commitlint_rules.as_ref(), &messages, &diff, candidates.max(1), template.as_deref()).await;
+        spinner.finish_and_clear();
+        
+        if add_candidates(&mut history, generated?) == 0 {
+            println!("{}", "The model came up with the same messages again".yellow());
         }
     };
     
-    if confirmed {
+    if let Some(commit_message) = selected {
         // Execute git commit
-        let spinner = ProgressBar::new_spinner();
-        spinner.set_style(
-            ProgressStyle::default_spinner()
-                .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-                .template("{spinner} {msg}")
-                .unwrap(),
-        );
-        spinner.set_message("Committing the changes");
-        spinner.enable_steady_tick(Duration::from_millis(100));
+        let spinner = new_spinner("Committing the changes");
         
         let mut commit_args = vec!["commit", "-m", &commit_message];
         for arg in extra_args {
@@ -185,7 +254,7 @@ pub async fn execute_commit(
         let output = Command::new("git")
             .args(&commit_args)
             .output()?;
-            
+        
         spinner.finish_with_message(format!("{} Successfully committed", "✓".green()));
         
         if !output.stdout.is_empty() {
@@ -198,7 +267,7 @@ pub async fn execute_commit(
             let remotes_output = Command::new("git")
                 .args(&["remote"])
                 .output()?;
-                
+            
             let remotes_str = String::from_utf8_lossy(&remotes_output.stdout);
             let remotes: Vec<&str> = remotes_str.lines().collect();
             
@@ -218,20 +287,12 @@ pub async fn execute_commit(
                 };
                 
                 if push_confirmed {
-                    let spinner = ProgressBar::new_spinner();
-                    spinner.set_style(
-                        ProgressStyle::default_spinner()
-                            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-                            .template("{spinner} {msg}")
-                            .unwrap(),
-                    );
-                    spinner.set_message(format!("Running 'git push {}'", remotes[0]));
-                    spinner.enable_steady_tick(Duration::from_millis(100));
+                    let spinner = new_spinner(format!("Running 'git push {}'", remotes[0]));
                     
                     let output = Command::new("git")
                         .args(&["push", "--verbose", remotes[0]])
                         .output()?;
-                        
+                    
                     spinner.finish_with_message(format!("{} Successfully pushed all commits to {}", "✓".green(), remotes[0]));
                     
                     if !output.stdout.is_empty() {
@@ -247,24 +308,16 @@ pub async fn execute_commit(
                 
                 let selected = Select::new("Choose a remote to push to:", options)
                     .prompt();
-                    
+                
                 match selected {
                     Ok(remote) => {
                         if remote != "don't push" {
-                            let spinner = ProgressBar::new_spinner();
-                            spinner.set_style(
-                                ProgressStyle::default_spinner()
-                                    .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-                                    .template("{spinner} {msg}")
-                                    .unwrap(),
-                            );
-                            spinner.set_message(format!("Running 'git push {}'", remote));
-                            spinner.enable_steady_tick(Duration::from_millis(100));
+                            let spinner = new_spinner(format!("Running 'git push {}'", remote));
                             
                             let output = Command::new("git")
                                 .args(&["push", remote])
                                 .output()?;
-                                
+                            
                             spinner.finish_with_message(format!("{} Successfully pushed all commits to {}", "✓".green(), remote));
                             
                             if !output.stdout.is_empty() {
@@ -278,20 +331,191 @@ pub async fn execute_commit(
                 }
             }
         }
-    } else {
-        // Ask if user wants to regenerate the message
-        let regenerate = match Confirm::new("Do you want to regenerate the message?")
-            .with_default(false)
-            .prompt() 
-        {
-            Ok(regenerate) => regenerate,
-            Err(_) => return Err(Error::UserCancelled),
-        };
+    }
+    
+    Ok(())
+}
+
+// An entry of the candidate picker
+enum CandidateChoice {
+    Candidate(usize, String),
+    Edit,
+    Regenerate,
+    Cancel,
+}
+
+fn candidate_choices(history: &[String]) -> Vec<CandidateChoice> {
+    history.iter()
+        .enumerate()
+        .map(|(index, message)| CandidateChoice::Candidate(index, message.clone()))
+        .collect()
+}
+
+impl std::fmt::Display for CandidateChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CandidateChoice::Candidate(index, message) => {
+                write!(f, "{}. {}", index + 1, message.lines().next().unwrap_or_default())
+            }
+            CandidateChoice::Edit => write!(f, "✎ Edit a candidate"),
+            CandidateChoice::Regenerate => write!(f, "↻ Generate new candidates"),
+            CandidateChoice::Cancel => write!(f, "✗ Cancel"),
+        }
+    }
+}
+
+// An answer when walking through the hunks
+#[derive(Clone, Copy)]
+enum HunkChoice {
+    Stage,
+    Skip,
+    PickLines,
+    StageFile,
+    SkipFile,
+    Done,
+}
+
+impl std::fmt::Display for HunkChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HunkChoice::Stage => write!(f, "Stage this hunk"),
+            HunkChoice::Skip => write!(f, "Skip this hunk"),
+            HunkChoice::PickLines => write!(f, "Pick the lines to stage"),
+            HunkChoice::StageFile => write!(f, "Stage the rest of this file"),
+            HunkChoice::SkipFile => write!(f, "Skip the rest of this file"),
+            HunkChoice::Done => write!(f, "Done, stage what was picked"),
+        }
+    }
+}
+
+// Walk through the unstaged hunks of the given files like `git add -p`, returning the ones to stage
+fn pick_hunks(repo: &Repository, files: &[String]) -> Result<Vec<Hunk>> {
+    let diff = get_unstaged_diff(repo)?;
+    let hunks: Vec<Hunk> = get_diff_hunks(&diff)?
+        .into_iter()
+        .filter(|hunk| files.contains(&hunk.path))
+        .collect();
+    
+    let mut selected = Vec::new();
+    let mut index = 0;
+    
+    while index < hunks.len() {
+        let hunk = &hunks[index];
+        let left_in_file = hunks[index..].iter().take_while(|h| h.path == hunk.path).count();
+        
+        println!("\n{} {}", hunk.path.bold(), format!("({}/{})", index + 1, hunks.len()).bright_black());
+        print!("{}", hunk.header.cyan());
+        for line in hunk.body.lines() {
+            match line.chars().next() {
+                Some('+') => println!("{}", line.green()),
+                Some('-') => println!("{}", line.red()),
+                _ => println!("{}", line),
+            }
+        }
+        
+        let mut choices = vec![HunkChoice::Stage, HunkChoice::Skip];
+        if !hunk.changed_lines().is_empty() {
+            choices.push(HunkChoice::PickLines);
+        }
+        if left_in_file > 1 {
+            choices.push(HunkChoice::StageFile);
+            choices.push(HunkChoice::SkipFile);
+        }
+        choices.push(HunkChoice::Done);
+        
+        let choice = Select::new("Stage this hunk?", choices)
+            .prompt()
+            .map_err(|_| Error::UserCancelled)?;
+        
+        match choice {
+            HunkChoice::Stage => selected.push(hunk.clone()),
+            HunkChoice::Skip => {}
+            HunkChoice::PickLines => {
+                let lines = MultiSelect::new("Select the lines to stage:", hunk.changed_lines())
+                    .raw_prompt()
+                    .map_err(|_| Error::UserCancelled)?;
+                
+                if !lines.is_empty() {
+                    let picked: Vec<usize> = lines.iter().map(|line| line.index).collect();
+                    selected.push(hunk.with_lines(&picked));
+                }
+            }
+            HunkChoice::StageFile => {
+                selected.extend(hunks[index..index + left_in_file].iter().cloned());
+                index += left_in_file;
+                continue;
+            }
+            HunkChoice::SkipFile => {
+                index += left_in_file;
+                continue;
+            }
+            HunkChoice::Done => break,
+        }
         
-        if regenerate {
-            return execute_commit(extra_args, context, false, full_gitmoji_spec, skip_confirmation).await;
+        index += 1;
+    }
+    
+    Ok(selected)
+}
+
+// Open the message in the git editor, nothing is committed when it is emptied
+fn edit_message(repo: &Repository, message: &str) -> Result<Option<String>> {
+    let edited = edit_commit_message(repo, message)?;
+    
+    if edited.is_empty() {
+        println!("{}", "Aborting commit due to empty commit message".yellow());
+        return Ok(None);
+    }
+    
+    Ok(Some(edited))
+}
+
+// Generate alternative messages, varying the sampling so they actually differ
+async fn generate_candidates(
+    config: &Config,
+    engine: &dyn AiEngine,
+    commitlint_rules: Option<&Value>,
+    messages: &[Message],
+    diff: &str,
+    count: usize,
+    template: Option<&str>,
+) -> Result<Vec<String>> {
+    let varied = get_commit_engine_with_sampling(config, Sampling::VARIED)?;
+    let generated = varied.generate_commit_messages(messages.to_vec(), diff, count).await?;
+    
+    let mut candidates = Vec::with_capacity(generated.len());
+    for mut commit_message in generated {
+        if let Some(rules) = commitlint_rules {
+            commit_message = enforce_commitlint_rules(engine, rules, messages, diff, &commit_message).await?;
+        }
+        
+        if let Some(template) = template {
+            commit_message = template.replace(&config.message_template_placeholder, &commit_message);
         }
+        
+        candidates.push(commit_message);
     }
     
-    Ok(())
+    Ok(candidates)
+}
+
+// Show the candidates that weren't seen before and add them to the history, returning how many there were
+fn add_candidates(history: &mut Vec<String>, candidates: Vec<String>) -> usize {
+    let mut added = 0;
+    
+    for candidate in candidates {
+        if history.contains(&candidate) {
+            continue;
+        }
+        
+        history.push(candidate);
+        added += 1;
+        
+        println!("\n{}", format!("Candidate {}:", history.len()).green());
+        println!("{}", "——————————————————".bright_black());
+        println!("{}", history[history.len() - 1]);
+        println!("{}", "——————————————————".bright_black());
+    }
+    
+    added
 }
\ No newline at end of file
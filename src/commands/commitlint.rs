@@ -1,13 +1,18 @@
 use crate::error::{Error, Result};
 use crate::cli::CommitlintAction;
 use crate::engine::get_engine;
-use crate::commands::config::Config;
+use crate::commands::config::{Config, PromptModule};
+use crate::engine::engine::{AiEngine, Message};
+use crate::modules::commitlint::{fix, lint, load_commitlint_config, Violation};
+use crate::modules::commitlint::lint::errors;
+use crate::modules::commitlint::prompts::infer_prompts_from_commitlint_config;
+use crate::utils::spinner::new_spinner;
 
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::str::FromStr;
 use colored::Colorize;
-use indicatif::{ProgressBar, ProgressStyle};
 use log::{info, error, debug};
 use serde::{Serialize, Deserialize};
 use serde_json::{json, Value};
@@ -15,11 +20,35 @@ use sha2::{Sha256, Digest};
 
 const COMMITLINT_LLM_CONFIG_PATH: &str = ".opencommit-commitlint";
 
+// How many times the engine is asked to fix a message breaking commitlint rules
+const MAX_REPAIR_ATTEMPTS: usize = 2;
+
 #[derive(Debug, Serialize, Deserialize)]
-struct CommitlintLLMConfig {
-    hash: String,
-    prompts: Vec<String>,
-    consistency: serde_json::Map<String, Value>,
+pub struct CommitlintLLMConfig {
+    pub hash: String,
+    pub prompts: Vec<String>,
+    pub consistency: serde_json::Map<String, Value>,
+}
+
+// Example commit messages generated for the commitlint rules in one language
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitlintConsistency {
+    #[serde(default)]
+    pub commit_fix: String,
+    #[serde(default)]
+    pub commit_feat: String,
+    #[serde(default)]
+    pub commit_description: String,
+}
+
+impl CommitlintLLMConfig {
+    // Get the consistency example for a language, falling back to any stored one
+    pub fn consistency_for(&self, local_language: &str) -> Option<CommitlintConsistency> {
+        self.consistency.get(local_language)
+            .or_else(|| self.consistency.values().next())
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+    }
 }
 
 // Calculate a hash for a string
@@ -50,6 +79,14 @@ async fn write_commitlint_llm_config(config: &CommitlintLLMConfig) -> Result<()>
 
 // Get commitlint config from project
 async fn get_commitlint_pwd_config() -> Result<Value> {
+    // Read the config natively, Node is only needed for presets we don't bundle
+    let native_error = match load_commitlint_config() {
+        Ok(config) => return Ok(config),
+        Err(e) => e,
+    };
+    
+    debug!("Falling back to `npx commitlint`: {}", native_error);
+    
     // Try to run commitlint --print-config
     let output = Command::new("npx")
         .args(&["commitlint", "--print-config"])
@@ -62,107 +99,18 @@ async fn get_commitlint_pwd_config() -> Result<Value> {
                 let config: Value = serde_json::from_str(&config_str)?;
                 Ok(config)
             } else {
-                Err(Error::CommitlintError(
-                    "Failed to get commitlint config. Make sure commitlint is installed.".to_string()
-                ))
+                Err(native_error)
             }
         }
         Err(_) => {
-            Err(Error::CommitlintError(
-                "Failed to run commitlint. Make sure commitlint is installed.".to_string()
-            ))
+            Err(native_error)
         }
     }
 }
 
-// Infer prompts from commitlint config
-fn infer_prompts_from_commitlint_config(config: &Value) -> Vec<String> {
-    let mut prompts = Vec::new();
-    
-    // Extract rules from config
-    if let Some(rules) = config.get("rules").and_then(|r| r.as_object()) {
-        for (rule_name, rule_config) in rules {
-            if let Some(rule_array) = rule_config.as_array() {
-                if rule_array.len() >= 2 {
-                    // Get severity (0 = disabled, 1 = warning, 2 = error)
-                    let severity = rule_array[0].as_i64().unwrap_or(0);
-                    if severity == 0 {
-                        continue; // Skip disabled rules
-                    }
-                    
-                    // Get applicable (true/false)
-                    let applicable = rule_array[1].as_bool().unwrap_or(true);
-                    let applicable_str = if applicable { "must" } else { "must not" };
-                    
-                    // Get value (if any)
-                    let value = if rule_array.len() > 2 { Some(&rule_array[2]) } else { None };
-                    
-                    // Generate prompt based on rule name
-                    let prompt = match rule_name.as_str() {
-                        "body-case" => {
-                            if let Some(case_value) = value {
-                                format!("The body should {} be in {} case.", applicable_str, case_value)
-                            } else {
-                                format!("The body should {} follow case rules.", applicable_str)
-                            }
-                        }
-                        "body-empty" => {
-                            format!("The body should {} be empty.", applicable_str)
-                        }
-                        "body-full-stop" => {
-                            if let Some(stop_value) = value {
-                                format!("The body should {} end with '{}'.", applicable_str, stop_value)
-                            } else {
-                                format!("The body should {} end with a full stop.", applicable_str)
-                            }
-                        }
-                        "body-leading-blank" => {
-                            format!("There should {} be a blank line at the beginning of the body.", applicable_str)
-                        }
-                        "body-max-length" => {
-                            if let Some(length) = value {
-                                format!("The body should {} have {} characters or less.", applicable_str, length)
-                            } else {
-                                format!("The body should {} have a maximum length.", applicable_str)
-                            }
-                        }
-                        "type-enum" => {
-                            if let Some(types) = value.and_then(|v| v.as_array()) {
-                                let types_str = types.iter()
-                                    .filter_map(|t| t.as_str())
-                                    .collect::<Vec<_>>()
-                                    .join(", ");
-                                format!("The type should {} be one of the following: {}.", applicable_str, types_str)
-                            } else {
-                                format!("The type should {} be from the allowed list.", applicable_str)
-                            }
-                        }
-                        // Add more rule handlers as needed
-                        _ => {
-                            format!("Rule '{}' should {} be followed.", rule_name, applicable_str)
-                        }
-                    };
-                    
-                    prompts.push(prompt);
-                }
-            }
-        }
-    }
-    
-    prompts
-}
-
 // Configure commitlint integration
 async fn configure_commitlint_integration(force: bool) -> Result<()> {
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-            .template("{spinner} {msg}")
-            .unwrap(),
-    );
-    spinner.set_message("Loading @commitlint configuration");
-    spinner.enable_steady_tick(std::time::Duration::from_millis(100));
+    let spinner = new_spinner("Loading @commitlint configuration");
     
     let file_exists = commitlint_llm_config_exists().await;
     
@@ -260,6 +208,88 @@ import {
     Ok(())
 }
 
+// Get the commitlint prompts for commit generation, regenerating them if the project config changed
+pub async fn load_commitlint_llm_config() -> Result<CommitlintLLMConfig> {
+    if commitlint_llm_config_exists().await {
+        let commitlint_config = get_commitlint_pwd_config().await?;
+        let hash = compute_hash(&serde_json::to_string(&commitlint_config)?);
+        
+        let existing_config = get_commitlint_llm_config().await?;
+        if existing_config.hash == hash {
+            return Ok(existing_config);
+        }
+        
+        info!("@commitlint configuration changed, regenerating {}", COMMITLINT_LLM_CONFIG_PATH);
+    }
+    
+    configure_commitlint_integration(true).await?;
+    get_commitlint_llm_config().await
+}
+
+// The project's commitlint rules when messages follow them, loaded once per command
+pub async fn load_commitlint_rules(config: &Config) -> Result<Option<Value>> {
+    if PromptModule::from_str(&config.prompt_module)? != PromptModule::Commitlint {
+        return Ok(None);
+    }
+    
+    get_commitlint_pwd_config().await.map(Some)
+}
+
+// Make a generated message follow the project's commitlint rules
+pub async fn enforce_commitlint_rules(
+    engine: &dyn AiEngine,
+    rules: &Value,
+    messages: &[Message],
+    diff: &str,
+    commit_message: &str,
+) -> Result<String> {
+    // Fix what doesn't need the model first
+    let mut commit_message = fix(commit_message, rules);
+    
+    for _ in 0..MAX_REPAIR_ATTEMPTS {
+        let violations = lint(&commit_message, rules);
+        let errors = errors(&violations);
+        if errors.is_empty() {
+            break;
+        }
+        
+        debug!("Generated message breaks {} @commitlint rules, asking for a fix", errors.len());
+        
+        let (repair_messages, repair_request) = repair_exchange(messages, diff, &commit_message, &errors);
+        
+        // A diff that only fit in chunks leaves no room for the exchange, keep the message as it is
+        let repaired = match engine.generate_commit_message(repair_messages, &repair_request).await {
+            Err(Error::TooManyTokens(tokens)) => {
+                debug!("Repair request needs {} tokens, keeping the message", tokens);
+                break;
+            }
+            repaired => repaired?,
+        };
+        commit_message = fix(&repaired, rules);
+    }
+    
+    for violation in lint(&commit_message, rules) {
+        println!("{} {}", "⚠".yellow(), violation);
+    }
+    
+    Ok(commit_message)
+}
+
+// The model sees the diff, its answer and what is wrong with it
+fn repair_exchange(messages: &[Message], diff: &str, commit_message: &str, errors: &[&Violation]) -> (Vec<Message>, String) {
+    let mut repair_messages = messages.to_vec();
+    repair_messages.push(Message::user(diff));
+    repair_messages.push(Message::assistant(commit_message));
+    
+    let repair_request = format!(
+        "This commit message does not follow the @commitlint rules:\n{}\n\n\
+        Rewrite the commit message so it follows all the rules, keeping its meaning. Reply with the commit message only.",
+        errors.iter().map(|e| format!("- {}", e)).collect::<Vec<_>>().join("\n")
+    );
+    
+    (repair_messages, repair_request)
+}
+
 // Handler for commitlint commands
 pub async fn handle_commitlint_command(action: CommitlintAction) -> Result<()> {
     println!("{}", "OpenCommit Commitlint".bright_blue());
@@ -281,4 +311,62 @@ pub async fn handle_commitlint_command(action: CommitlintAction) -> Result<()> {
             configure_commitlint_integration(true).await
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::engine::{EngineConfig, Sampling};
+    use crate::engine::openai::build_chat_completion_request;
+    use crate::modules::commitlint::rules::RuleLevel;
+    
+    #[test]
+    fn openai_repairs_see_the_diff_and_the_rejected_message() {
+        let config = EngineConfig {
+            api_key: "sk-test".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            max_tokens_output: 500,
+            max_tokens_input: 4096,
+            base_url: None,
+            azure_deployment: None,
+            azure_api_version: None,
+            flowise_flow_id: None,
+            request_timeout: None,
+            max_retries: 0,
+            sampling: Sampling::DETERMINISTIC,
+        };
+        let messages = vec![
+            Message::system("Write commit messages."),
+            Message::user("diff --git a/example.rs b/example.rs"),
+            Message::assistant("feat: example"),
+        ];
+        let violation = Violation {
+            rule: "type-enum".to_string(),
+            level: RuleLevel::Error,
+            message: "type must be one of [feat, fix]".to_string(),
+        };
+        
+        let (repair_messages, repair_request) = repair_exchange(
+            &messages,
+            "diff --git a/src/lib.rs b/src/lib.rs",
+            "update: lib",
+            &[&violation],
+        );
+        let request = build_chat_completion_request(&config, &repair_messages, &repair_request).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+        
+        let sent: Vec<(&str, &str)> = body["messages"].as_array().unwrap().iter()
+            .map(|m| (m["role"].as_str().unwrap(), m["content"].as_str().unwrap()))
+            .collect();
+        assert_eq!(sent[..5], [
+            ("system", "Write commit messages."),
+            ("user", "diff --git a/example.rs b/example.rs"),
+            ("assistant", "feat: example"),
+            ("user", "diff --git a/src/lib.rs b/src/lib.rs"),
+            ("assistant", "update: lib"),
+        ]);
+        assert_eq!(sent[5].0, "user");
+        assert!(sent[5].1.contains("- type must be one of [feat, fix] [type-enum]"));
+        assert_eq!(sent.len(), 6);
+    }
+}
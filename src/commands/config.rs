@@ -1,9 +1,12 @@
 use crate::error::{Error, Result};
-use crate::cli::ConfigAction;
+use crate::cli::{ConfigAction, ProfileAction, SecretAction};
+use crate::engine::openai::{find_compatible_provider, AuthStyle, CompatibleProvider};
+use crate::modules::secrets::{self, SecretBackend};
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
+use std::io::{IsTerminal, Read};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use once_cell::sync::Lazy;
@@ -29,6 +32,13 @@ pub enum ConfigKey {
     OcoApiUrl,
     OcoGitpush,
     OcoWhy,
+    OcoAzureDeployment,
+    OcoAzureApiVersion,
+    OcoFlowiseFlowId,
+    OcoProfile,
+    OcoFallback,
+    OcoRequestTimeout,
+    OcoMaxRetries,
 }
 
 impl FromStr for ConfigKey {
@@ -50,6 +60,13 @@ impl FromStr for ConfigKey {
             "OCO_API_URL" => Ok(ConfigKey::OcoApiUrl),
             "OCO_GITPUSH" => Ok(ConfigKey::OcoGitpush),
             "OCO_WHY" => Ok(ConfigKey::OcoWhy),
+            "OCO_AZURE_DEPLOYMENT" => Ok(ConfigKey::OcoAzureDeployment),
+            "OCO_AZURE_API_VERSION" => Ok(ConfigKey::OcoAzureApiVersion),
+            "OCO_FLOWISE_FLOW_ID" => Ok(ConfigKey::OcoFlowiseFlowId),
+            "OCO_PROFILE" => Ok(ConfigKey::OcoProfile),
+            "OCO_FALLBACK" => Ok(ConfigKey::OcoFallback),
+            "OCO_REQUEST_TIMEOUT" => Ok(ConfigKey::OcoRequestTimeout),
+            "OCO_MAX_RETRIES" => Ok(ConfigKey::OcoMaxRetries),
             _ => Err(Error::InvalidConfiguration(format!("Unknown config key: {}", s))),
         }
     }
@@ -72,23 +89,26 @@ impl ToString for ConfigKey {
             ConfigKey::OcoApiUrl => "OCO_API_URL",
             ConfigKey::OcoGitpush => "OCO_GITPUSH",
             ConfigKey::OcoWhy => "OCO_WHY",
+            ConfigKey::OcoAzureDeployment => "OCO_AZURE_DEPLOYMENT",
+            ConfigKey::OcoAzureApiVersion => "OCO_AZURE_API_VERSION",
+            ConfigKey::OcoFlowiseFlowId => "OCO_FLOWISE_FLOW_ID",
+            ConfigKey::OcoProfile => "OCO_PROFILE",
+            ConfigKey::OcoFallback => "OCO_FALLBACK",
+            ConfigKey::OcoRequestTimeout => "OCO_REQUEST_TIMEOUT",
+            ConfigKey::OcoMaxRetries => "OCO_MAX_RETRIES",
         }.to_string()
     }
 }
 
-// Enum for AI providers
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+// Enum for AI providers, OpenAI-compatible vendors come from the table in engine/openai.rs
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AiProvider {
-    OpenAi,
+    Compatible(&'static CompatibleProvider),
     Anthropic,
     Azure,
     Ollama,
     Gemini,
     Flowise,
-    Groq,
-    Mistral,
-    Mlx,
-    Deepseek,
     Test,
 }
 
@@ -97,18 +117,26 @@ impl FromStr for AiProvider {
     
     fn from_str(s: &str) -> Result<Self> {
         match s.to_lowercase().as_str() {
-            "openai" => Ok(AiProvider::OpenAi),
             "anthropic" => Ok(AiProvider::Anthropic),
             "azure" => Ok(AiProvider::Azure),
             "ollama" => Ok(AiProvider::Ollama),
             "gemini" => Ok(AiProvider::Gemini),
             "flowise" => Ok(AiProvider::Flowise),
-            "groq" => Ok(AiProvider::Groq),
-            "mistral" => Ok(AiProvider::Mistral),
-            "mlx" => Ok(AiProvider::Mlx),
-            "deepseek" => Ok(AiProvider::Deepseek),
             "test" => Ok(AiProvider::Test),
-            _ => Err(Error::UnsupportedAiProvider(s.to_string())),
+            name => find_compatible_provider(name)
+                .map(AiProvider::Compatible)
+                .ok_or_else(|| Error::UnsupportedAiProvider(s.to_string())),
+        }
+    }
+}
+
+impl AiProvider {
+    // Whether the provider refuses requests without OCO_API_KEY
+    pub fn requires_api_key(&self) -> bool {
+        match self {
+            AiProvider::Compatible(provider) => provider.auth != AuthStyle::None,
+            AiProvider::Ollama | AiProvider::Flowise | AiProvider::Test => false,
+            AiProvider::Anthropic | AiProvider::Azure | AiProvider::Gemini => true,
         }
     }
 }
@@ -116,16 +144,12 @@ impl FromStr for AiProvider {
 impl ToString for AiProvider {
     fn to_string(&self) -> String {
         match self {
-            AiProvider::OpenAi => "openai",
+            AiProvider::Compatible(provider) => provider.name,
             AiProvider::Anthropic => "anthropic",
             AiProvider::Azure => "azure",
             AiProvider::Ollama => "ollama",
             AiProvider::Gemini => "gemini",
             AiProvider::Flowise => "flowise",
-            AiProvider::Groq => "groq",
-            AiProvider::Mistral => "mistral",
-            AiProvider::Mlx => "mlx",
-            AiProvider::Deepseek => "deepseek",
             AiProvider::Test => "test",
         }.to_string()
     }
@@ -203,6 +227,30 @@ pub struct Config {
     
     #[serde(rename = "OCO_WHY")]
     pub why: bool,
+    
+    #[serde(rename = "OCO_AZURE_DEPLOYMENT")]
+    pub azure_deployment: Option<String>,
+    
+    #[serde(rename = "OCO_AZURE_API_VERSION")]
+    pub azure_api_version: Option<String>,
+    
+    #[serde(rename = "OCO_FLOWISE_FLOW_ID")]
+    pub flowise_flow_id: Option<String>,
+    
+    #[serde(rename = "OCO_PROFILE")]
+    pub profile: Option<String>,
+    
+    // Comma separated profile names or `provider[:model]` entries tried when the provider fails
+    #[serde(rename = "OCO_FALLBACK")]
+    pub fallback: Option<String>,
+    
+    // Seconds, each engine has its own default
+    #[serde(rename = "OCO_REQUEST_TIMEOUT")]
+    pub request_timeout: Option<u64>,
+    
+    // Retries of rate limited, failed or unreachable requests
+    #[serde(rename = "OCO_MAX_RETRIES")]
+    pub max_retries: u32,
 }
 
 impl Default for Config {
@@ -222,6 +270,13 @@ impl Default for Config {
             api_url: None,
             gitpush: true,
             why: false,
+            azure_deployment: None,
+            azure_api_version: None,
+            flowise_flow_id: None,
+            profile: None,
+            fallback: None,
+            request_timeout: None,
+            max_retries: 3,
         }
     }
 }
@@ -229,13 +284,260 @@ impl Default for Config {
 // Global config instance
 static CONFIG: Lazy<Mutex<Option<Config>>> = Lazy::new(|| Mutex::new(None));
 
+// Name of the per-repository config file
+pub const LOCAL_CONFIG_FILE: &str = ".opencommit.toml";
+
+// Table holding the named profiles in a config file
+pub const PROFILES_KEY: &str = "profiles";
+
+// Where an effective config value comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigOrigin {
+    Default,
+    Global(PathBuf),
+    Local(PathBuf),
+    Profile(String),
+    Env,
+}
+
+impl ConfigOrigin {
+    // The file a value was read from
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ConfigOrigin::Global(path) | ConfigOrigin::Local(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigOrigin::Default => write!(f, "default"),
+            ConfigOrigin::Global(path) => write!(f, "global:{}", path.display()),
+            ConfigOrigin::Local(path) => write!(f, "local:{}", path.display()),
+            ConfigOrigin::Profile(name) => write!(f, "profile:{}", name),
+            ConfigOrigin::Env => write!(f, "env"),
+        }
+    }
+}
+
+impl ConfigKey {
+    pub const ALL: [ConfigKey; 21] = [
+        ConfigKey::OcoApiKey,
+        ConfigKey::OcoTokensMaxInput,
+        ConfigKey::OcoTokensMaxOutput,
+        ConfigKey::OcoDescription,
+        ConfigKey::OcoEmoji,
+        ConfigKey::OcoModel,
+        ConfigKey::OcoLanguage,
+        ConfigKey::OcoMessageTemplateplaceholder,
+        ConfigKey::OcoPromptModule,
+        ConfigKey::OcoAiProvider,
+        ConfigKey::OcoOneLineCommit,
+        ConfigKey::OcoApiUrl,
+        ConfigKey::OcoGitpush,
+        ConfigKey::OcoWhy,
+        ConfigKey::OcoAzureDeployment,
+        ConfigKey::OcoAzureApiVersion,
+        ConfigKey::OcoFlowiseFlowId,
+        ConfigKey::OcoProfile,
+        ConfigKey::OcoFallback,
+        ConfigKey::OcoRequestTimeout,
+        ConfigKey::OcoMaxRetries,
+    ];
+    
+    // Keys whose values are masked and kept out of config files
+    pub fn is_secret(&self) -> bool {
+        matches!(self, ConfigKey::OcoApiKey)
+    }
+    
+    // Keys deciding where the API key is sent, a cloned repository must not be able to change them
+    pub fn is_allowed_in_repository(&self) -> bool {
+        !matches!(
+            self,
+            ConfigKey::OcoApiKey | ConfigKey::OcoApiUrl | ConfigKey::OcoAiProvider | ConfigKey::OcoFallback
+        )
+    }
+    
+    // Convert a raw value to the TOML type the config field expects
+    pub fn parse_value(&self, value: &str) -> Option<toml::Value> {
+        match self {
+            ConfigKey::OcoTokensMaxInput | ConfigKey::OcoTokensMaxOutput |
+            ConfigKey::OcoRequestTimeout | ConfigKey::OcoMaxRetries => {
+                value.parse::<i64>().ok().map(toml::Value::Integer)
+            },
+            ConfigKey::OcoDescription | ConfigKey::OcoEmoji | ConfigKey::OcoOneLineCommit |
+            ConfigKey::OcoGitpush | ConfigKey::OcoWhy => {
+                value.parse::<bool>().ok().map(toml::Value::Boolean)
+            },
+            _ => Some(toml::Value::String(value.to_string())),
+        }
+    }
+}
+
+// Read a config file as a table, a missing file is an empty table
+pub fn read_config_table(path: &Path) -> Result<toml::Table> {
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+    
+    let content = fs::read_to_string(path)?;
+    Ok(toml::from_str(&content)?)
+}
+
+pub fn write_config_table(path: &Path, table: &toml::Table) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    
+    fs::write(path, toml::to_string(table)?)?;
+    Ok(())
+}
+
+// Merge a layer over the current values and remember where they came from
+fn apply_layer(
+    table: &mut toml::Table,
+    origins: &mut HashMap<ConfigKey, ConfigOrigin>,
+    layer: toml::Table,
+    origin: &ConfigOrigin,
+) {
+    for (name, value) in layer {
+        if let Ok(key) = ConfigKey::from_str(&name) {
+            origins.insert(key, origin.clone());
+        }
+        table.insert(name, value);
+    }
+}
+
+// Move the `[profiles.<name>]` tables of a config file layer into `profiles`, later files override earlier ones
+fn take_profiles(layer: &mut toml::Table, profiles: &mut toml::Table) {
+    let Some(toml::Value::Table(defined)) = layer.remove(PROFILES_KEY) else {
+        return;
+    };
+    
+    for (name, values) in defined {
+        let toml::Value::Table(values) = values else {
+            continue;
+        };
+        
+        match profiles.get_mut(&name) {
+            Some(toml::Value::Table(existing)) => existing.extend(values),
+            _ => {
+                profiles.insert(name, toml::Value::Table(values));
+            }
+        }
+    }
+}
+
+// Apply the profile named by the environment layer or, failing that, by the config files
+fn apply_profile(
+    table: &mut toml::Table,
+    origins: &mut HashMap<ConfigKey, ConfigOrigin>,
+    mut profiles: toml::Table,
+    env_layer: &toml::Table,
+) -> Result<()> {
+    let profile_key = ConfigKey::OcoProfile.to_string();
+    let active_profile = env_layer.get(&profile_key)
+        .or_else(|| table.get(&profile_key))
+        .and_then(|v| v.as_str())
+        .map(String::from);
+    
+    let Some(name) = active_profile else {
+        return Ok(());
+    };
+    
+    let Some(toml::Value::Table(mut values)) = profiles.remove(&name) else {
+        return Err(Error::InvalidConfiguration(format!("Unknown profile: {}", name)));
+    };
+    values.remove(&profile_key);
+    apply_layer(table, origins, values, &ConfigOrigin::Profile(name));
+    
+    Ok(())
+}
+
+// Read a config file layer, logging and skipping files that fail to parse
+fn read_layer(path: &Path) -> Option<toml::Table> {
+    match read_config_table(path) {
+        Ok(table) => Some(table),
+        Err(e) => {
+            error!("Failed to parse config {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+// Drop the keys a repository file may not set, from its settings and its profiles, returning their names
+fn restrict_repository_layer(layer: &mut toml::Table) -> Vec<String> {
+    let denied = |name: &str| ConfigKey::from_str(name).is_ok_and(|key| !key.is_allowed_in_repository());
+    
+    let mut removed: Vec<String> = layer.keys().filter(|name| denied(name)).cloned().collect();
+    layer.retain(|name, _| !denied(name));
+    
+    if let Some(toml::Value::Table(profiles)) = layer.get_mut(PROFILES_KEY) {
+        for (profile, values) in profiles.iter_mut() {
+            if let toml::Value::Table(values) = values {
+                removed.extend(values.keys().filter(|name| denied(name)).map(|name| format!("{}.{}.{}", PROFILES_KEY, profile, name)));
+                values.retain(|name, _| !denied(name));
+            }
+        }
+    }
+    
+    removed
+}
+
+// Read the layer of a config file, a repository file can't redirect the API key
+fn read_origin_layer(origin: &ConfigOrigin) -> Option<toml::Table> {
+    let mut layer = read_layer(origin.path()?)?;
+    
+    if let ConfigOrigin::Local(path) = origin {
+        let removed = restrict_repository_layer(&mut layer);
+        if !removed.is_empty() {
+            eprintln!(
+                "{}",
+                format!("Ignoring {} in {}, set them in the global config or the environment", removed.join(", "), path.display()).yellow()
+            );
+        }
+    }
+    
+    Some(layer)
+}
+
 impl Config {
     pub fn global_config_path() -> PathBuf {
         home_dir().unwrap_or_default().join(".opencommit")
     }
     
-    pub fn local_config_path() -> PathBuf {
-        Path::new(".env").to_path_buf()
+    // Nearest .opencommit.toml between the working directory and the git root
+    pub fn local_config_path() -> Option<PathBuf> {
+        let cwd = std::env::current_dir().ok()?;
+        
+        for dir in cwd.ancestors() {
+            let path = dir.join(LOCAL_CONFIG_FILE);
+            if path.is_file() {
+                return Some(path);
+            }
+            
+            // Don't look outside of the repository
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        
+        None
+    }
+    
+    // File written by `config set --local`, the existing one or a new one at the git root
+    pub fn local_config_target() -> Result<PathBuf> {
+        if let Some(path) = Self::local_config_path() {
+            return Ok(path);
+        }
+        
+        let cwd = std::env::current_dir()?;
+        cwd.ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .map(|dir| dir.join(LOCAL_CONFIG_FILE))
+            .ok_or(Error::NotGitRepository)
     }
     
     pub fn load() -> Result<Self> {
@@ -244,138 +546,252 @@ impl Config {
             return Ok(config);
         }
         
-        // Load dotenv if exists
-        let _ = dotenv::dotenv();
+        let (config, _) = Self::load_with_origins()?;
         
-        // Start with default config
-        let mut config = Config::default();
-        
-        // Load global config if exists
-        let global_path = Self::global_config_path();
-        if global_path.exists() {
-            let content = fs::read_to_string(&global_path)?;
-            match toml::from_str::<Config>(&content) {
-                Ok(global_config) => {
-                    // Merge global config into default
-                    config = global_config;
-                }
-                Err(e) => {
-                    // Log error but continue with defaults
-                    error!("Failed to parse global config: {}", e);
-                }
-            }
-        }
-        
-        // Override with environment variables (from .env or actual env)
-        if let Ok(key) = std::env::var("OCO_API_KEY") {
-            config.api_key = Some(key);
-        }
+        // Cache the config
+        *CONFIG.lock().unwrap() = Some(config.clone());
         
-        if let Ok(val) = std::env::var("OCO_TOKENS_MAX_INPUT") {
-            if let Ok(num) = val.parse::<usize>() {
-                config.tokens_max_input = num;
-            }
+        Ok(config)
+    }
+    
+    // Config files that can hold settings and profiles, lowest precedence first
+    pub fn config_files() -> Vec<ConfigOrigin> {
+        let mut files = vec![ConfigOrigin::Global(Self::global_config_path())];
+        if let Some(local_path) = Self::local_config_path() {
+            files.push(ConfigOrigin::Local(local_path));
         }
+        files
+    }
+    
+    // Layer defaults, the global config, the repository config, the active profile and the environment, in that order
+    pub fn load_with_origins() -> Result<(Self, HashMap<ConfigKey, ConfigOrigin>)> {
+        // Load dotenv if exists
+        let _ = dotenv::dotenv();
         
-        if let Ok(val) = std::env::var("OCO_TOKENS_MAX_OUTPUT") {
-            if let Ok(num) = val.parse::<usize>() {
-                config.tokens_max_output = num;
-            }
-        }
+        let mut table = match toml::Value::try_from(Config::default())? {
+            toml::Value::Table(table) => table,
+            _ => toml::Table::new(),
+        };
+        let mut origins = HashMap::new();
+        let mut profiles = toml::Table::new();
         
-        if let Ok(val) = std::env::var("OCO_DESCRIPTION") {
-            if let Ok(b) = val.parse::<bool>() {
-                config.description = b;
+        for origin in Self::config_files() {
+            if let Some(mut layer) = read_origin_layer(&origin) {
+                take_profiles(&mut layer, &mut profiles);
+                apply_layer(&mut table, &mut origins, layer, &origin);
             }
         }
         
-        if let Ok(val) = std::env::var("OCO_EMOJI") {
-            if let Ok(b) = val.parse::<bool>() {
-                config.emoji = b;
+        // Override with environment variables (from .env or actual env), ignoring unparsable values
+        let mut env_layer = toml::Table::new();
+        for key in ConfigKey::ALL {
+            if let Ok(raw) = std::env::var(key.to_string()) {
+                if let Some(value) = key.parse_value(&raw) {
+                    env_layer.insert(key.to_string(), value);
+                }
             }
         }
         
-        if let Ok(val) = std::env::var("OCO_MODEL") {
-            config.model = val;
-        }
+        // The profile picked by --profile/OCO_PROFILE or the config files sits right below the environment
+        apply_profile(&mut table, &mut origins, profiles, &env_layer)?;
+        apply_layer(&mut table, &mut origins, env_layer, &ConfigOrigin::Env);
         
-        if let Ok(val) = std::env::var("OCO_LANGUAGE") {
-            config.language = val;
-        }
+        let config = toml::Value::Table(table).try_into::<Config>()?;
         
-        if let Ok(val) = std::env::var("OCO_MESSAGE_TEMPLATE_PLACEHOLDER") {
-            config.message_template_placeholder = val;
-        }
+        Ok((config, origins))
+    }
+    
+    // Defaults and the global config only, for rewriting the global file without baking in other layers
+    pub fn load_global() -> Result<Self> {
+        let mut config = toml::Value::try_from(Config::default())?;
         
-        if let Ok(val) = std::env::var("OCO_PROMPT_MODULE") {
-            config.prompt_module = val;
+        if let (toml::Value::Table(table), Some(layer)) = (&mut config, read_layer(&Self::global_config_path())) {
+            table.extend(layer);
         }
         
-        if let Ok(val) = std::env::var("OCO_AI_PROVIDER") {
-            config.ai_provider = val;
+        Ok(config.try_into::<Config>()?)
+    }
+    
+    // Drop the cached config so the next load sees changed files
+    pub fn clear_cache() {
+        *CONFIG.lock().unwrap() = None;
+    }
+    
+    // Effective value of a key as shown by `config get`
+    pub fn get(&self, key: &ConfigKey) -> String {
+        match key {
+            ConfigKey::OcoApiKey => self.api_key.clone().unwrap_or_default(),
+            ConfigKey::OcoTokensMaxInput => self.tokens_max_input.to_string(),
+            ConfigKey::OcoTokensMaxOutput => self.tokens_max_output.to_string(),
+            ConfigKey::OcoDescription => self.description.to_string(),
+            ConfigKey::OcoEmoji => self.emoji.to_string(),
+            ConfigKey::OcoModel => self.model.clone(),
+            ConfigKey::OcoLanguage => self.language.clone(),
+            ConfigKey::OcoMessageTemplateplaceholder => self.message_template_placeholder.clone(),
+            ConfigKey::OcoPromptModule => self.prompt_module.clone(),
+            ConfigKey::OcoAiProvider => self.ai_provider.clone(),
+            ConfigKey::OcoOneLineCommit => self.one_line_commit.to_string(),
+            ConfigKey::OcoApiUrl => self.api_url.clone().unwrap_or_default(),
+            ConfigKey::OcoGitpush => self.gitpush.to_string(),
+            ConfigKey::OcoWhy => self.why.to_string(),
+            ConfigKey::OcoAzureDeployment => self.azure_deployment.clone().unwrap_or_default(),
+            ConfigKey::OcoAzureApiVersion => self.azure_api_version.clone().unwrap_or_default(),
+            ConfigKey::OcoFlowiseFlowId => self.flowise_flow_id.clone().unwrap_or_default(),
+            ConfigKey::OcoProfile => self.profile.clone().unwrap_or_default(),
+            ConfigKey::OcoFallback => self.fallback.clone().unwrap_or_default(),
+            ConfigKey::OcoRequestTimeout => self.request_timeout.map(|t| t.to_string()).unwrap_or_default(),
+            ConfigKey::OcoMaxRetries => self.max_retries.to_string(),
         }
-        
-        if let Ok(val) = std::env::var("OCO_ONE_LINE_COMMIT") {
-            if let Ok(b) = val.parse::<bool>() {
-                config.one_line_commit = b;
+    }
+    
+    // This config with a profile applied on top
+    pub fn with_profile(&self, name: &str) -> Result<Config> {
+        let mut profiles = toml::Table::new();
+        for origin in Self::config_files() {
+            if let Some(mut layer) = read_origin_layer(&origin) {
+                take_profiles(&mut layer, &mut profiles);
             }
         }
         
-        if let Ok(val) = std::env::var("OCO_API_URL") {
-            config.api_url = Some(val);
+        let Some(toml::Value::Table(values)) = profiles.remove(name) else {
+            return Err(Error::InvalidConfiguration(format!("Unknown profile: {}", name)));
+        };
+        
+        let mut config = toml::Value::try_from(self)?;
+        if let toml::Value::Table(table) = &mut config {
+            table.extend(values);
         }
         
-        if let Ok(val) = std::env::var("OCO_GITPUSH") {
-            if let Ok(b) = val.parse::<bool>() {
-                config.gitpush = b;
-            }
+        Ok(config.try_into::<Config>()?)
+    }
+    
+    // Configs to try in order when the provider fails
+    pub fn fallback_configs(&self) -> Result<Vec<Config>> {
+        let Some(fallback) = &self.fallback else {
+            return Ok(Vec::new());
+        };
+        
+        fallback.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| self.fallback_config(entry, &|name| std::env::var(name).ok()))
+            .collect()
+    }
+    
+    // A `provider[:model]` entry keeps the rest of this config, a profile name brings its own settings,
+    // `env_var` looks up the provider keys
+    fn fallback_config(&self, entry: &str, env_var: &dyn Fn(&str) -> Option<String>) -> Result<Config> {
+        let (provider, model) = match entry.split_once(':') {
+            Some((provider, model)) => (provider, Some(model)),
+            None => (entry, None),
+        };
+        
+        if AiProvider::from_str(provider).is_err() {
+            return self.with_profile(entry);
         }
         
-        if let Ok(val) = std::env::var("OCO_WHY") {
-            if let Ok(b) = val.parse::<bool>() {
-                config.why = b;
-            }
+        let mut config = self.clone();
+        config.fallback = None;
+        
+        // Keys and URLs belong to the provider they were set for
+        if provider != self.ai_provider {
+            config.api_key = provider_api_key(provider, env_var)?;
+            config.api_url = None;
         }
         
-        // Cache the config
-        *CONFIG.lock().unwrap() = Some(config.clone());
+        config.ai_provider = provider.to_string();
+        config.model = model
+            .map(String::from)
+            .unwrap_or_else(|| Config::default_model_for_provider(provider));
         
         Ok(config)
     }
     
+    // Providers that need a key can't be used without one, checked before any work is done
+    pub fn engine_ready(&self) -> Result<()> {
+        if self.api_key.is_none() && AiProvider::from_str(&self.ai_provider)?.requires_api_key() {
+            return Err(Error::NoApiKey);
+        }
+        
+        Ok(())
+    }
+    
+    // The API key, looked up in the secret store when the config only names it
+    pub fn resolved_api_key(&self) -> Result<Option<String>> {
+        match self.api_key.as_deref() {
+            Some(value) => match secrets::parse_reference(value) {
+                Some(name) => secrets::get(name).map(Some),
+                None => Ok(Some(value.to_string())),
+            },
+            None => Ok(None),
+        }
+    }
+    
     pub fn save(&self) -> Result<()> {
         let path = Self::global_config_path();
+        let mut config = self.clone();
         
-        // Create parent directory if it doesn't exist
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
+        // Keep the API key out of the file when there is somewhere safer to put it
+        if let Some(api_key) = config.api_key.clone().filter(|k| secrets::parse_reference(k).is_none()) {
+            match secrets::store(&config.ai_provider, &api_key, None) {
+                Ok(backend) => {
+                    config.api_key = Some(secrets::reference(&config.ai_provider));
+                    println!(
+                        "{} {} as '{}', {} now refers to it",
+                        format!("✓ Moved {} to", ConfigKey::OcoApiKey.to_string()).green(),
+                        backend,
+                        config.ai_provider,
+                        path.display()
+                    );
+                }
+                Err(e) => error!("Failed to store the API key securely, keeping it in {}: {}", path.display(), e),
+            }
+        }
+        
+        // Keep the profiles, they aren't part of the struct
+        let mut table = read_config_table(&path)?;
+        if let toml::Value::Table(values) = toml::Value::try_from(&config)? {
+            table.extend(values);
         }
         
         // Write config to file
-        let content = toml::to_string(self)?;
-        fs::write(&path, content)?;
+        write_config_table(&path, &table)?;
         
-        // Update cached config
-        *CONFIG.lock().unwrap() = Some(self.clone());
+        // Other layers may override what was written, load again on next use
+        Self::clear_cache();
         
         Ok(())
     }
     
     // Helper to get default model for a provider
     pub fn default_model_for_provider(provider: &str) -> String {
+        if let Some(compatible) = find_compatible_provider(provider) {
+            return compatible.default_model.to_string();
+        }
+        
         match provider.to_lowercase().as_str() {
-            "openai" => "gpt-4o-mini".to_string(),
             "anthropic" => "claude-3-5-sonnet-20240620".to_string(),
             "gemini" => "gemini-1.5-flash".to_string(),
-            "groq" => "llama3-70b-8192".to_string(), 
-            "mistral" => "mistral-small-latest".to_string(),
-            "deepseek" => "deepseek-chat".to_string(),
             "ollama" => "mistral".to_string(),
             _ => "gpt-4o-mini".to_string(),
         }
     }
 }
 
+// Key of a provider other than the configured one, from OCO_<PROVIDER>_API_KEY or the secret named after the provider
+fn provider_api_key(provider: &str, env_var: &dyn Fn(&str) -> Option<String>) -> Result<Option<String>> {
+    if let Some(api_key) = env_var(&format!("OCO_{}_API_KEY", provider.to_uppercase())) {
+        return Ok(Some(api_key));
+    }
+    
+    // A missing secret skips the fallback with a warning naming it
+    if AiProvider::from_str(provider)?.requires_api_key() {
+        return Ok(Some(secrets::reference(provider)));
+    }
+    
+    Ok(None)
+}
+
 // Validators for config values
 pub fn validate_config(key: &ConfigKey, value: &str) -> Result<String> {
     match key {
@@ -464,83 +880,537 @@ pub fn validate_config(key: &ConfigKey, value: &str) -> Result<String> {
                 Err(_) => Err(Error::InvalidConfiguration("Why must be a boolean".to_string())),
             }
         },
+        ConfigKey::OcoAzureDeployment => {
+            if value.is_empty() {
+                Err(Error::InvalidConfiguration("Azure deployment cannot be empty".to_string()))
+            } else {
+                Ok(value.to_string())
+            }
+        },
+        ConfigKey::OcoAzureApiVersion => {
+            if value.is_empty() {
+                Err(Error::InvalidConfiguration("Azure API version cannot be empty".to_string()))
+            } else {
+                Ok(value.to_string())
+            }
+        },
+        ConfigKey::OcoFlowiseFlowId => {
+            if value.is_empty() {
+                Err(Error::InvalidConfiguration("Flowise flow id cannot be empty".to_string()))
+            } else {
+                Ok(value.to_string())
+            }
+        },
+        ConfigKey::OcoProfile => {
+            if value.is_empty() {
+                Err(Error::InvalidConfiguration("Profile name cannot be empty".to_string()))
+            } else {
+                Ok(value.to_string())
+            }
+        },
+        ConfigKey::OcoFallback => {
+            let profiles = defined_profiles()?;
+            
+            for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                let provider = entry.split(':').next().unwrap_or_default();
+                if AiProvider::from_str(provider).is_err() && !profiles.iter().any(|(name, _)| name == entry) {
+                    return Err(Error::InvalidConfiguration(format!(
+                        "Fallback '{}' is neither a provider nor a profile",
+                        entry
+                    )));
+                }
+            }
+            
+            Ok(value.to_string())
+        },
+        ConfigKey::OcoRequestTimeout => {
+            match value.parse::<u64>() {
+                Ok(seconds) if seconds > 0 => Ok(value.to_string()),
+                _ => Err(Error::InvalidConfiguration("Request timeout must be a positive number of seconds".to_string())),
+            }
+        },
+        ConfigKey::OcoMaxRetries => {
+            match value.parse::<u32>() {
+                Ok(_) => Ok(value.to_string()),
+                Err(_) => Err(Error::InvalidConfiguration("Max retries must be a number".to_string())),
+            }
+        },
+    }
+}
+
+// Parse and validate a KEY=VALUE argument
+fn parse_key_value(kv: &str) -> Result<(ConfigKey, toml::Value)> {
+    let parts: Vec<&str> = kv.splitn(2, '=').collect();
+    if parts.len() != 2 {
+        return Err(Error::InvalidConfiguration(format!("Invalid key-value pair: {}", kv)));
+    }
+    
+    let key = ConfigKey::from_str(parts[0])?;
+    let value = validate_config(&key, parts[1])?;
+    let value = key.parse_value(&value)
+        .ok_or_else(|| Error::InvalidConfiguration(format!("Invalid value for {}: {}", key.to_string(), value)))?;
+    
+    Ok((key, value))
+}
+
+// Name a secret after what it belongs to, secrets of repository configs also after the repository
+fn secret_name(base: &str, target: &Path) -> String {
+    if target == Config::global_config_path() {
+        return base.to_string();
+    }
+    
+    match target.parent() {
+        Some(repo) => format!("{}@{}", base, repo.display()),
+        None => base.to_string(),
+    }
+}
+
+// Put a literal secret in the secret store and return the reference that goes in the config file instead
+fn protect_secret(key: &ConfigKey, value: toml::Value, name: &str) -> Result<toml::Value> {
+    let Some(secret) = value.as_str().filter(|v| key.is_secret() && secrets::parse_reference(v).is_none()) else {
+        return Ok(value);
+    };
+    
+    let backend = secrets::store(name, secret, None)?;
+    println!("{} {} as '{}'", format!("✓ Stored {} in", key.to_string()).green(), backend, name);
+    
+    Ok(toml::Value::String(secrets::reference(name)))
+}
+
+// Keep the last characters so keys can still be told apart
+fn mask_secret(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= 8 {
+        return "*".repeat(8);
+    }
+    
+    format!("{}{}", "*".repeat(8), chars[chars.len() - 4..].iter().collect::<String>())
+}
+
+// Value as shown by `config get` and `config list`, secrets are masked unless revealed
+fn display_value(config: &Config, key: &ConfigKey, reveal: bool) -> Result<String> {
+    let value = config.get(key);
+    if !key.is_secret() || value.is_empty() {
+        return Ok(value);
+    }
+    
+    match (secrets::parse_reference(&value), reveal) {
+        // A reference gives nothing away
+        (Some(_), false) => Ok(value),
+        (Some(name), true) => secrets::get(name),
+        (None, true) => Ok(value),
+        (None, false) => Ok(mask_secret(&value)),
+    }
+}
+
+// File written by commands with a --local switch
+fn config_target(local: bool) -> Result<PathBuf> {
+    if local {
+        Config::local_config_target()
+    } else {
+        Ok(Config::global_config_path())
     }
 }
 
+// Refuse to write keys to a repository file that would be ignored when loading it
+fn check_allowed_in_target(key: &ConfigKey, local: bool) -> Result<()> {
+    if local && !key.is_allowed_in_repository() {
+        return Err(Error::InvalidConfiguration(format!(
+            "{} cannot be set in {}, set it in the global config instead",
+            key.to_string(),
+            LOCAL_CONFIG_FILE
+        )));
+    }
+    
+    Ok(())
+}
+
+// Profiles defined across the config files, with the file that defines them
+fn defined_profiles() -> Result<Vec<(String, ConfigOrigin)>> {
+    let mut profiles: Vec<(String, ConfigOrigin)> = Vec::new();
+    
+    for origin in Config::config_files() {
+        let Some(path) = origin.path() else {
+            continue;
+        };
+        
+        if let Some(toml::Value::Table(defined)) = read_config_table(path)?.get(PROFILES_KEY) {
+            for name in defined.keys() {
+                profiles.retain(|(existing, _)| existing != name);
+                profiles.push((name.clone(), origin.clone()));
+            }
+        }
+    }
+    
+    Ok(profiles)
+}
+
 // Handler for config commands
 pub async fn handle_config_command(action: ConfigAction) -> Result<()> {
     match action {
-        ConfigAction::Get { keys } => {
+        ConfigAction::Get { keys, reveal } => {
             let config = Config::load()?;
             
             for key_str in keys {
                 let key = ConfigKey::from_str(&key_str)?;
-                let value = match key {
-                    ConfigKey::OcoApiKey => config.api_key.unwrap_or_default(),
-                    ConfigKey::OcoTokensMaxInput => config.tokens_max_input.to_string(),
-                    ConfigKey::OcoTokensMaxOutput => config.tokens_max_output.to_string(),
-                    ConfigKey::OcoDescription => config.description.to_string(),
-                    ConfigKey::OcoEmoji => config.emoji.to_string(),
-                    ConfigKey::OcoModel => config.model,
-                    ConfigKey::OcoLanguage => config.language,
-                    ConfigKey::OcoMessageTemplateplaceholder => config.message_template_placeholder,
-                    ConfigKey::OcoPromptModule => config.prompt_module,
-                    ConfigKey::OcoAiProvider => config.ai_provider,
-                    ConfigKey::OcoOneLineCommit => config.one_line_commit.to_string(),
-                    ConfigKey::OcoApiUrl => config.api_url.unwrap_or_default(),
-                    ConfigKey::OcoGitpush => config.gitpush.to_string(),
-                    ConfigKey::OcoWhy => config.why.to_string(),
-                };
-                
-                println!("{}={}", key.to_string(), value);
+                println!("{}={}", key.to_string(), display_value(&config, &key, reveal)?);
             }
             
             Ok(())
         },
-        ConfigAction::Set { key_values } => {
-            let mut config = Config::load()?;
+        ConfigAction::Set { key_values, local, .. } => {
+            let config = Config::load()?;
+            let path = config_target(local)?;
+            
+            // Only the given keys are written, values from other layers stay where they are
+            let mut table = read_config_table(&path)?;
             
             for kv in key_values {
-                let parts: Vec<&str> = kv.splitn(2, '=').collect();
-                if parts.len() != 2 {
-                    return Err(Error::InvalidConfiguration(format!("Invalid key-value pair: {}", kv)));
-                }
-                
-                let key = ConfigKey::from_str(parts[0])?;
-                let value = validate_config(&key, parts[1])?;
+                let (key, value) = parse_key_value(&kv)?;
+                check_allowed_in_target(&key, local)?;
                 
-                // Update config
-                match key {
-                    ConfigKey::OcoApiKey => config.api_key = Some(value),
-                    ConfigKey::OcoTokensMaxInput => config.tokens_max_input = value.parse().unwrap(),
-                    ConfigKey::OcoTokensMaxOutput => config.tokens_max_output = value.parse().unwrap(),
-                    ConfigKey::OcoDescription => config.description = value.parse().unwrap(),
-                    ConfigKey::OcoEmoji => config.emoji = value.parse().unwrap(),
-                    ConfigKey::OcoModel => config.model = value,
-                    ConfigKey::OcoLanguage => config.language = value,
-                    ConfigKey::OcoMessageTemplateplaceholder => config.message_template_placeholder = value,
-                    ConfigKey::OcoPromptModule => config.prompt_module = value,
-                    ConfigKey::OcoAiProvider => {
-                        config.ai_provider = value;
-                        // Update model if needed
-                        if config.model.is_empty() || 
-                           config.model == "gpt-4o-mini" ||
-                           config.model == "claude-3-5-sonnet-20240620" {
-                            config.model = Config::default_model_for_provider(&config.ai_provider);
-                        }
-                    },
-                    ConfigKey::OcoOneLineCommit => config.one_line_commit = value.parse().unwrap(),
-                    ConfigKey::OcoApiUrl => config.api_url = Some(value),
-                    ConfigKey::OcoGitpush => config.gitpush = value.parse().unwrap(),
-                    ConfigKey::OcoWhy => config.why = value.parse().unwrap(),
+                if key == ConfigKey::OcoAiProvider {
+                    // Update model if needed
+                    if config.model.is_empty() || 
+                       config.model == "gpt-4o-mini" ||
+                       config.model == "claude-3-5-sonnet-20240620" {
+                        table.insert(
+                            ConfigKey::OcoModel.to_string(),
+                            toml::Value::String(Config::default_model_for_provider(value.as_str().unwrap_or_default())),
+                        );
+                    }
                 }
+                
+                table.insert(key.to_string(), value);
+            }
+            
+            // Keep API keys out of the file, named after the provider they belong to
+            let api_key_name = ConfigKey::OcoApiKey.to_string();
+            if let Some(value) = table.remove(&api_key_name) {
+                let provider = table.get(&ConfigKey::OcoAiProvider.to_string())
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(&config.ai_provider)
+                    .to_string();
+                let value = protect_secret(&ConfigKey::OcoApiKey, value, &secret_name(&provider, &path))?;
+                table.insert(api_key_name, value);
             }
             
             // Save updated config
-            config.save()?;
+            write_config_table(&path, &table)?;
+            Config::clear_cache();
+            
+            println!("{} {}", "✓ Config successfully set in".green(), path.display());
+            Ok(())
+        },
+        ConfigAction::List { show_origin, reveal } => {
+            let (config, origins) = Config::load_with_origins()?;
+            
+            for key in ConfigKey::ALL {
+                let value = display_value(&config, &key, reveal)?;
+                if show_origin {
+                    let origin = origins.get(&key).cloned().unwrap_or(ConfigOrigin::Default);
+                    println!("{}\t{}={}", origin.to_string().dimmed(), key.to_string(), value);
+                } else {
+                    println!("{}={}", key.to_string(), value);
+                }
+            }
             
-            println!("{}", "✓ Config successfully set".green());
             Ok(())
+        },
+        ConfigAction::Profile { action } => handle_profile_command(action).await,
+        ConfigAction::Secret { action } => handle_secret_command(action).await,
+    }
+}
+
+// Handler for profile commands
+async fn handle_profile_command(action: ProfileAction) -> Result<()> {
+    let profile_key = ConfigKey::OcoProfile.to_string();
+    
+    match action {
+        ProfileAction::List => {
+            let profiles = defined_profiles()?;
+            if profiles.is_empty() {
+                println!("No profiles defined, create one with `rac config profile create <name> KEY=VALUE...`");
+                return Ok(());
+            }
+            
+            let active = Config::load()?.profile;
+            for (name, origin) in profiles {
+                let marker = if active.as_deref() == Some(name.as_str()) { "*" } else { " " };
+                println!("{} {} {}", marker, name, format!("({})", origin).dimmed());
+            }
+            
+            Ok(())
+        },
+        ProfileAction::Use { name, local } => {
+            if !defined_profiles()?.iter().any(|(existing, _)| *existing == name) {
+                return Err(Error::InvalidConfiguration(format!("Unknown profile: {}", name)));
+            }
+            
+            let path = config_target(local)?;
+            let mut table = read_config_table(&path)?;
+            table.insert(profile_key, toml::Value::String(name.clone()));
+            write_config_table(&path, &table)?;
+            Config::clear_cache();
+            
+            println!("{} {}", "✓ Using profile".green(), name);
+            Ok(())
+        },
+        ProfileAction::Create { name, local, key_values } => {
+            let path = config_target(local)?;
+            let mut table = read_config_table(&path)?;
+            
+            let mut values = toml::Table::new();
+            for kv in key_values {
+                let (key, value) = parse_key_value(&kv)?;
+                if key == ConfigKey::OcoProfile {
+                    return Err(Error::InvalidConfiguration("A profile cannot select another profile".to_string()));
+                }
+                check_allowed_in_target(&key, local)?;
+                let value = protect_secret(&key, value, &secret_name(&format!("profile.{}", name), &path))?;
+                values.insert(key.to_string(), value);
+            }
+            
+            let profiles = table.entry(PROFILES_KEY)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            let Some(profiles) = profiles.as_table_mut() else {
+                return Err(Error::InvalidConfiguration(format!("'{}' in {} is not a table", PROFILES_KEY, path.display())));
+            };
+            
+            if profiles.contains_key(&name) {
+                return Err(Error::InvalidConfiguration(format!("Profile {} already exists", name)));
+            }
+            profiles.insert(name.clone(), toml::Value::Table(values));
+            
+            write_config_table(&path, &table)?;
+            
+            println!("{} {} in {}", "✓ Created profile".green(), name, path.display());
+            Ok(())
+        },
+        ProfileAction::Delete { name, local } => {
+            let path = config_target(local)?;
+            let mut table = read_config_table(&path)?;
+            
+            let Some(toml::Value::Table(profiles)) = table.get_mut(PROFILES_KEY) else {
+                return Err(Error::InvalidConfiguration(format!("Profile {} is not defined in {}", name, path.display())));
+            };
+            if profiles.remove(&name).is_none() {
+                return Err(Error::InvalidConfiguration(format!("Profile {} is not defined in {}", name, path.display())));
+            }
+            if profiles.is_empty() {
+                table.remove(PROFILES_KEY);
+            }
+            
+            // Don't leave the file pointing at a profile that is gone
+            if table.get(&profile_key).and_then(|v| v.as_str()) == Some(name.as_str()) {
+                table.remove(&profile_key);
+            }
+            
+            write_config_table(&path, &table)?;
+            Config::clear_cache();
+            
+            println!("{} {}", "✓ Deleted profile".green(), name);
+            Ok(())
+        },
+    }
+}
+
+// Handler for secret commands
+async fn handle_secret_command(action: SecretAction) -> Result<()> {
+    match action {
+        SecretAction::Set { name, file } => {
+            // Prompt people, read pipes so CI can store a key without a terminal
+            let secret = if std::io::stdin().is_terminal() {
+                match inquire::Password::new(&format!("Value of secret '{}':", name))
+                    .without_confirmation()
+                    .with_display_mode(inquire::PasswordDisplayMode::Masked)
+                    .prompt()
+                {
+                    Ok(secret) => secret,
+                    Err(_) => return Err(Error::UserCancelled),
+                }
+            } else {
+                let mut secret = String::new();
+                std::io::stdin().read_to_string(&mut secret)?;
+                secret.trim().to_string()
+            };
+            
+            if secret.is_empty() {
+                return Err(Error::InvalidConfiguration("Secret cannot be empty".to_string()));
+            }
+            
+            let backend = secrets::store(&name, &secret, file.then_some(SecretBackend::File))?;
+            
+            println!("{} {} in {}", "✓ Stored secret".green(), name, backend);
+            println!("Use it with `rac config set OCO_API_KEY={}`", secrets::reference(&name));
+            Ok(())
+        },
+        SecretAction::Delete { name } => {
+            secrets::delete(&name)?;
+            
+            println!("{} {}", "✓ Deleted secret".green(), name);
+            Ok(())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn table(content: &str) -> toml::Table {
+        toml::from_str(content).unwrap()
+    }
+    
+    #[test]
+    fn repository_layer_cannot_redirect_the_api_key() {
+        let mut config = match toml::Value::try_from(Config::default()).unwrap() {
+            toml::Value::Table(table) => table,
+            _ => unreachable!(),
+        };
+        let mut origins = HashMap::new();
+        
+        let global = table(r#"
+            OCO_AI_PROVIDER = "openai"
+            OCO_API_KEY = "secret:openai"
+        "#);
+        apply_layer(&mut config, &mut origins, global, &ConfigOrigin::Global(PathBuf::from("/home/.opencommit")));
+        
+        let mut local = table(r#"
+            OCO_API_URL = "https://attacker.example"
+            OCO_AI_PROVIDER = "anthropic"
+            OCO_API_KEY = "secret:anthropic"
+            OCO_FALLBACK = "ollama"
+            OCO_MODEL = "gpt-4o"
+            
+            [profiles.evil]
+            OCO_API_URL = "https://attacker.example"
+            OCO_LANGUAGE = "de"
+        "#);
+        let removed = restrict_repository_layer(&mut local);
+        assert_eq!(removed.len(), 5);
+        
+        let mut profiles = toml::Table::new();
+        take_profiles(&mut local, &mut profiles);
+        let local_origin = ConfigOrigin::Local(PathBuf::from("/repo/.opencommit.toml"));
+        apply_layer(&mut config, &mut origins, local, &local_origin);
+        
+        let config = toml::Value::Table(config).try_into::<Config>().unwrap();
+        assert_eq!(config.api_url, None);
+        assert_eq!(config.ai_provider, "openai");
+        assert_eq!(config.api_key.as_deref(), Some("secret:openai"));
+        assert_eq!(config.fallback, None);
+        assert_eq!(config.model, "gpt-4o");
+        assert_eq!(origins.get(&ConfigKey::OcoModel), Some(&local_origin));
+        
+        let evil = profiles.get("evil").and_then(|v| v.as_table()).unwrap();
+        assert!(!evil.contains_key("OCO_API_URL"));
+        assert!(evil.contains_key("OCO_LANGUAGE"));
+    }
+    
+    #[test]
+    fn compatible_providers_come_from_the_table() {
+        for compatible in crate::engine::openai::COMPATIBLE_PROVIDERS {
+            let provider = AiProvider::from_str(&compatible.name.to_uppercase()).unwrap();
+            assert_eq!(provider, AiProvider::Compatible(compatible));
+            assert_eq!(provider.to_string(), compatible.name);
+            assert_eq!(provider.requires_api_key(), compatible.auth != AuthStyle::None);
         }
+        
+        assert_eq!(AiProvider::from_str("anthropic").unwrap(), AiProvider::Anthropic);
+        assert!(!AiProvider::from_str("ollama").unwrap().requires_api_key());
+        assert!(AiProvider::from_str("unknown").is_err());
+    }
+    
+    #[test]
+    fn fallback_providers_use_their_own_key() {
+        let config = Config {
+            api_key: Some("sk-openai".to_string()),
+            api_url: Some("https://proxy.example/v1".to_string()),
+            ..Config::default()
+        };
+        
+        let env = HashMap::from([("OCO_DEEPSEEK_API_KEY", "sk-deepseek")]);
+        let env_var = |name: &str| env.get(name).map(|v| v.to_string());
+        
+        let same = config.fallback_config("openai:gpt-4o", &env_var).unwrap();
+        assert_eq!(same.api_key.as_deref(), Some("sk-openai"));
+        assert_eq!(same.api_url.as_deref(), Some("https://proxy.example/v1"));
+        assert_eq!(same.model, "gpt-4o");
+        
+        let anthropic = config.fallback_config("anthropic:claude-3-5-haiku-latest", &env_var).unwrap();
+        assert_eq!(anthropic.api_key.as_deref(), Some("secret:anthropic"));
+        assert_eq!(anthropic.api_url, None);
+        assert_eq!(anthropic.model, "claude-3-5-haiku-latest");
+        
+        let deepseek = config.fallback_config("deepseek", &env_var).unwrap();
+        assert_eq!(deepseek.api_key.as_deref(), Some("sk-deepseek"));
+        assert_eq!(deepseek.model, Config::default_model_for_provider("deepseek"));
+        
+        let ollama = config.fallback_config("ollama", &env_var).unwrap();
+        assert_eq!(ollama.api_key, None);
+    }
+    
+    #[test]
+    fn secrets_are_masked() {
+        assert_eq!(mask_secret("short"), "********");
+        assert_eq!(mask_secret("sk-1234567890abcd"), "********abcd");
+        
+        let config = Config { api_key: Some("sk-1234567890abcd".to_string()), ..Config::default() };
+        assert_eq!(display_value(&config, &ConfigKey::OcoApiKey, false).unwrap(), "********abcd");
+        assert_eq!(display_value(&config, &ConfigKey::OcoApiKey, true).unwrap(), "sk-1234567890abcd");
+        
+        // References give nothing away
+        let config = Config { api_key: Some("secret:openai".to_string()), ..Config::default() };
+        assert_eq!(display_value(&config, &ConfigKey::OcoApiKey, false).unwrap(), "secret:openai");
+    }
+    
+    #[test]
+    fn profiles_merge_across_files_and_follow_the_environment() {
+        let mut config = table("");
+        let mut origins = HashMap::new();
+        let mut profiles = toml::Table::new();
+        
+        let mut global = table(r#"
+            OCO_MODEL = "gpt-4o-mini"
+            OCO_PROFILE = "work"
+            
+            [profiles.work]
+            OCO_MODEL = "gpt-4o"
+            OCO_LANGUAGE = "de"
+        "#);
+        take_profiles(&mut global, &mut profiles);
+        apply_layer(&mut config, &mut origins, global, &ConfigOrigin::Global(PathBuf::from("/home/.opencommit")));
+        
+        let mut local = table(r#"
+            [profiles.work]
+            OCO_LANGUAGE = "fr"
+            
+            [profiles.home]
+            OCO_EMOJI = true
+        "#);
+        take_profiles(&mut local, &mut profiles);
+        assert!(!local.contains_key(PROFILES_KEY));
+        
+        // The files pick `work`, later files override single values of it
+        apply_profile(&mut config, &mut origins, profiles.clone(), &table("")).unwrap();
+        assert_eq!(config["OCO_MODEL"].as_str(), Some("gpt-4o"));
+        assert_eq!(config["OCO_LANGUAGE"].as_str(), Some("fr"));
+        assert_eq!(origins.get(&ConfigKey::OcoLanguage), Some(&ConfigOrigin::Profile("work".to_string())));
+        
+        // The environment picks another one
+        let env = table(r#"OCO_PROFILE = "home""#);
+        apply_profile(&mut config, &mut origins, profiles.clone(), &env).unwrap();
+        assert_eq!(config["OCO_EMOJI"].as_bool(), Some(true));
+        
+        let env = table(r#"OCO_PROFILE = "missing""#);
+        assert!(apply_profile(&mut config, &mut origins, profiles, &env).is_err());
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn repository_keys_are_refused_for_local_writes() {
+        assert!(check_allowed_in_target(&ConfigKey::OcoApiUrl, true).is_err());
+        assert!(check_allowed_in_target(&ConfigKey::OcoApiUrl, false).is_ok());
+        assert!(check_allowed_in_target(&ConfigKey::OcoModel, true).is_ok());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::error::{Error, Result};
 use crate::cli::HookAction;
+use crate::utils::spinner::new_spinner;
 
 use std::fs;
 use std::os::unix::fs::PermissionsExt;
@@ -37,6 +38,16 @@ fn get_hooks_path() -> Result<PathBuf> {
 
 // Check if current process is being called as a hook
 pub fn is_hook_called() -> bool {
+    // Git runs the symlink by its hook name, current_exe() would resolve it to the binary
+    let invoked_as_hook = std::env::args_os()
+        .next()
+        .map(|arg0| Path::new(&arg0).file_name() == Some(HOOK_NAME.as_ref()))
+        .unwrap_or(false);
+    
+    if invoked_as_hook {
+        return true;
+    }
+    
     match std::env::current_exe() {
         Ok(exe_path) => {
             if let Ok(hooks_path) = get_hooks_path() {
@@ -148,7 +159,17 @@ pub async fn handle_hook_command(action: HookAction) -> Result<()> {
 }
 
 // Function to handle prepare-commit-msg hook
-pub async fn prepare_commit_msg_hook(commit_msg_file: &str) -> Result<()> {
+//
+// Git passes the message file, then optionally the message source and, for amends, the commit sha.
+// Messages that already come from somewhere (-m, merges, squashes, amends) are left untouched.
+pub async fn prepare_commit_msg_hook(commit_msg_file: &str, commit_source: Option<&str>) -> Result<()> {
+    if let Some(source) = commit_source {
+        if source != "template" {
+            info!("Skipping commit message generation for '{}' commit", source);
+            return Ok(());
+        }
+    }
+    
     println!("{}", "OpenCommit Hook".bright_blue());
     
     // Check if commit message file exists
@@ -169,29 +190,25 @@ pub async fn prepare_commit_msg_hook(commit_msg_file: &str) -> Result<()> {
     // Load config
     let config = crate::commands::config::Config::load()?;
     
-    if config.api_key.is_none() && config.ai_provider != "ollama" && config.ai_provider != "test" {
+    if let Err(Error::NoApiKey) = config.engine_ready() {
         println!("No OCO_API_KEY is set. Set your key via `oco config set OCO_API_KEY=<value>. For more info see https://github.com/yourusername/opencommit-rs");
         return Ok(());
     }
     
     // Show spinner
-    let spinner = indicatif::ProgressBar::new_spinner();
-    spinner.set_style(
-        indicatif::ProgressStyle::default_spinner()
-            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-            .template("{spinner} {msg}")
-            .unwrap(),
-    );
-    spinner.set_message("Generating commit message");
-    spinner.enable_steady_tick(std::time::Duration::from_millis(100));
+    let spinner = new_spinner("Generating commit message");
     
     // Get diff
     let diff = crate::utils::git::get_diff(&repo, &staged_files)?;
     
     // Generate commit message
     let messages = crate::prompts::get_main_commit_prompt(false, String::new()).await?;
-    let engine = crate::engine::get_engine(&config)?;
-    let commit_message = engine.generate_commit_message(messages, &diff).await?;
+    let engine = crate::engine::get_commit_engine(&config)?;
+    let mut commit_message = engine.generate_commit_message(messages.clone(), &diff).await?;
+    
+    if let Some(rules) = crate::commands::commitlint::load_commitlint_rules(&config).await? {
+        commit_message = crate::commands::commitlint::enforce_commitlint_rules(engine.as_ref(), &rules, &messages, &diff, &commit_message).await?;
+    }
     
     spinner.finish_with_message("Done");
     
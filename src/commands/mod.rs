@@ -1,4 +1,8 @@
 pub mod commit;
 pub mod config;
 pub mod githook;
-pub mod commitlint;
\ No newline at end of file
+pub mod commitlint;
+pub mod split;
+pub mod reword;
+pub mod squash;
+pub mod pr;
\ No newline at end of file
@@ -0,0 +1,296 @@
+use crate::error::{Error, Result};
+use crate::engine::get_engine;
+use crate::engine::chunked::split_by_prefix;
+use crate::engine::engine::Message;
+use crate::i18n::get_translation;
+use crate::utils::token_count::token_count;
+use crate::utils::git::{assert_git_repo, get_default_branch, get_merge_base, get_commits_since, get_tree_diff};
+use crate::commands::config::Config;
+use crate::utils::spinner::new_spinner;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use colored::Colorize;
+
+// Where GitHub looks for a pull request template, relative to the repository root
+const TEMPLATE_PATHS: &[&str] = &[
+    ".github/pull_request_template.md",
+    ".github/PULL_REQUEST_TEMPLATE.md",
+    "pull_request_template.md",
+    "PULL_REQUEST_TEMPLATE.md",
+    "docs/pull_request_template.md",
+    "docs/PULL_REQUEST_TEMPLATE.md",
+];
+
+// Directories holding several templates, one of which is picked with --template
+const TEMPLATE_DIRS: &[&str] = &[
+    ".github/PULL_REQUEST_TEMPLATE",
+    "PULL_REQUEST_TEMPLATE",
+    "docs/PULL_REQUEST_TEMPLATE",
+];
+
+// Tokens kept for the note about the files left out of the diff
+const OMITTED_NOTE_TOKENS: usize = 50;
+
+// Sections of the description when the repository has no template
+const DEFAULT_SECTIONS: &str = "## Summary\n\n## Changes\n\n## Testing\n";
+
+// Generate a pull request title and description for the current branch
+pub async fn execute_pr(
+    base: Option<String>,
+    template: Option<String>,
+    output: Option<PathBuf>,
+    context: Option<String>,
+) -> Result<()> {
+    // Only the pull request goes to stdout so it can be piped
+    eprintln!("{}", "OpenCommit".bright_blue().bold());
+
+    let repo = assert_git_repo()?;
+    let base = match base {
+        Some(base) => base,
+        None => get_default_branch(&repo)?,
+    };
+    let head = repo.head()?.peel_to_commit()?;
+    let merge_base = get_merge_base(&repo, &base)?;
+
+    let commits = get_commits_since(&repo, &merge_base)?;
+    if commits.is_empty() {
+        eprintln!("{}", format!("No commits on this branch since {}", base).yellow());
+        return Ok(());
+    }
+
+    let diff = get_tree_diff(&repo, Some(&merge_base.tree()?), &head.tree()?)?;
+
+    let config = Config::load()?;
+    config.engine_ready()?;
+
+    let template = match repo.workdir() {
+        Some(root) => find_template(root, template.as_deref())?,
+        None => None,
+    };
+    let messages = get_pr_prompt(&config, template.as_deref(), context.as_deref())?;
+
+    // The commit log gives the intent, the diff the details
+    let log = commits.iter()
+        .map(|commit| {
+            let message = String::from_utf8_lossy(commit.message_bytes()).trim_end().to_string();
+            format!("- {}", message.replace('\n', "\n  "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let log = format!("Commits:\n{}\n\nDiff:\n", log);
+
+    // A description can't be pieced together from chunks like a commit message, the diff is cut to fit instead
+    let prompt_tokens = messages.iter().map(|m| token_count(&m.content)).sum::<usize>() + token_count(&log);
+    let Some(budget) = config.tokens_max_input
+        .checked_sub(config.tokens_max_output + prompt_tokens + OMITTED_NOTE_TOKENS)
+        .filter(|budget| *budget > 0)
+    else {
+        return Err(Error::TooManyTokens(prompt_tokens));
+    };
+    let request = format!("{}{}", log, fit_diff(&diff, budget));
+
+    let engine = get_engine(&config)?;
+
+    let spinner = new_spinner(format!("Describing {} commits since {}", commits.len(), base));
+
+    let generated = engine.generate_commit_message(messages, &request).await;
+    spinner.finish_and_clear();
+    let (title, body) = split_title(&generated?)?;
+
+    let pull_request = if body.is_empty() {
+        format!("{}\n", title)
+    } else {
+        format!("{}\n\n{}\n", title, body)
+    };
+
+    match output {
+        Some(path) => {
+            fs::write(&path, &pull_request)?;
+            eprintln!("{} Pull request written to {}", "✓".green(), path.display());
+        }
+        None => print!("{}", pull_request),
+    }
+
+    Ok(())
+}
+
+// The repository's pull request template, if it has one
+fn find_template(root: &Path, name: Option<&str>) -> Result<Option<String>> {
+    let path = match name {
+        Some(name) => Some(find_named_template(root, name)?),
+        None => match TEMPLATE_PATHS.iter().map(|path| root.join(path)).find(|path| path.is_file()) {
+            Some(path) => Some(path),
+            None => find_only_template(root)?,
+        },
+    };
+
+    Ok(path
+        .and_then(|path| fs::read_to_string(path).ok())
+        .filter(|template| !template.trim().is_empty()))
+}
+
+// Markdown templates of the template directories
+fn directory_templates(root: &Path) -> Vec<PathBuf> {
+    let mut templates: Vec<PathBuf> = TEMPLATE_DIRS.iter()
+        .filter_map(|dir| fs::read_dir(root.join(dir)).ok())
+        .flat_map(|entries| entries.flatten().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("md")))
+        .collect();
+    templates.sort();
+    templates
+}
+
+fn template_name(path: &Path) -> String {
+    path.file_stem().unwrap_or_default().to_string_lossy().to_string()
+}
+
+fn find_named_template(root: &Path, name: &str) -> Result<PathBuf> {
+    let templates = directory_templates(root);
+    let name = name.trim_end_matches(".md");
+
+    templates.iter()
+        .find(|path| template_name(path) == name)
+        .cloned()
+        .ok_or_else(|| Error::InvalidConfiguration(format!(
+            "No pull request template named {}, the templates are: {}",
+            name,
+            template_names(&templates)
+        )))
+}
+
+// Without --template a directory can only be used when it holds a single template
+fn find_only_template(root: &Path) -> Result<Option<PathBuf>> {
+    let mut templates = directory_templates(root);
+
+    match templates.len() {
+        0 => Ok(None),
+        1 => Ok(templates.pop()),
+        _ => Err(Error::InvalidConfiguration(format!(
+            "The repository has several pull request templates, pick one with --template: {}",
+            template_names(&templates)
+        ))),
+    }
+}
+
+fn template_names(templates: &[PathBuf]) -> String {
+    match templates {
+        [] => "none".to_string(),
+        _ => templates.iter().map(|path| template_name(path)).collect::<Vec<_>>().join(", "),
+    }
+}
+
+// Whole file diffs while they fit the budget, saying how many were left out
+fn fit_diff(diff: &str, budget: usize) -> String {
+    if token_count(diff) <= budget {
+        return diff.to_string();
+    }
+
+    let mut fitted = String::new();
+    let mut used = 0;
+    let mut omitted = 0;
+
+    for file_diff in split_by_prefix(diff, "diff --git ") {
+        let tokens = token_count(&file_diff);
+        if used + tokens <= budget {
+            fitted.push_str(&file_diff);
+            used += tokens;
+        } else {
+            omitted += 1;
+        }
+    }
+
+    let note = format!("\n[The diff of {} more files was left out to fit the model's context]\n", omitted);
+    format!("{}{}", fitted, note)
+}
+
+fn get_pr_prompt(config: &Config, template: Option<&str>, context: Option<&str>) -> Result<Vec<Message>> {
+    let translation = get_translation(&config.language)?;
+
+    let sections = match template {
+        Some(template) => format!(
+            "Fill in the repository's pull request template below. Keep its headings in the same order, \
+            replace its placeholder text and comments with content, and check the checklist items the changes satisfy.\n\
+            <template>\n{}\n</template>",
+            template.trim_end()
+        ),
+        None => format!(
+            "Use these Markdown sections: a summary of what the pull request does and why, \
+            a bullet list of the changes, and notes on how the changes were or can be tested.\n{}",
+            DEFAULT_SECTIONS
+        ),
+    };
+
+    let user_context = match context {
+        Some(context) => format!("\nAdditional context provided by the user: <context>{}</context>", context),
+        None => String::new(),
+    };
+
+    let system_content = format!(
+        "You are to act as the author of a pull request. I'll send you the commits of a branch and the diff of the branch against its base, \
+        and you are to write the pull request title and description.\n\
+        Reply with the title alone on the first line, under 72 characters and without any Markdown, then an empty line, then the description in Markdown.\n\
+        {}\n\
+        Do not make up tests that the commits and diff don't show, say what should be checked instead. Use {} for the pull request.{}",
+        sections,
+        translation.local_language,
+        user_context
+    );
+
+    Ok(vec![Message::system(system_content)])
+}
+
+// Separate the title line from the description
+fn split_title(text: &str) -> Result<(String, String)> {
+    let text = text.trim();
+
+    // Models like to wrap the whole answer in a code block
+    let text = text.strip_prefix("```markdown")
+        .or_else(|| text.strip_prefix("```md"))
+        .or_else(|| text.strip_prefix("```"))
+        .and_then(|inner| inner.strip_suffix("```"))
+        .map(str::trim)
+        .unwrap_or(text);
+
+    let (title, body) = text.split_once('\n').unwrap_or((text, ""));
+    let title = title.trim()
+        .trim_start_matches('#')
+        .trim()
+        .trim_start_matches("Title:")
+        .trim()
+        .to_string();
+
+    if title.is_empty() {
+        return Err(Error::EmptyCommitMessage);
+    }
+
+    Ok((title, body.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_the_title_from_the_description() {
+        let (title, body) = split_title("```markdown\n# Title: Add rac pr\n\n## Summary\nDescribe branches\n```").unwrap();
+        assert_eq!(title, "Add rac pr");
+        assert_eq!(body, "## Summary\nDescribe branches");
+
+        assert_eq!(split_title("Fix the parser").unwrap(), ("Fix the parser".to_string(), String::new()));
+        assert!(split_title("  \n").is_err());
+    }
+
+    #[test]
+    fn cuts_the_diff_by_file() {
+        let file = |name: &str| format!("diff --git a/{name} b/{name}\n--- a/{name}\n+++ b/{name}\n@@ -1 +1 @@\n-old\n+new\n");
+        let diff = format!("{}{}{}", file("a.rs"), file("b.rs"), file("c.rs"));
+
+        assert_eq!(fit_diff(&diff, token_count(&diff)), diff);
+
+        let fitted = fit_diff(&diff, token_count(&file("a.rs")) + 1);
+        assert!(fitted.starts_with(&file("a.rs")));
+        assert!(!fitted.contains("b.rs"));
+        assert!(fitted.contains("The diff of 2 more files was left out"));
+    }
+}
@@ -0,0 +1,318 @@
+use crate::error::{Error, Result};
+use crate::engine::get_commit_engine;
+use crate::prompts::get_main_commit_prompt;
+use crate::utils::git::{assert_git_repo, get_commit_diff};
+use crate::commands::commitlint::{enforce_commitlint_rules, load_commitlint_rules};
+use crate::commands::config::Config;
+use crate::utils::spinner::new_spinner;
+
+use std::collections::HashMap;
+use colored::Colorize;
+use console::{pad_str, truncate_str, Alignment, Term};
+use git2::{BranchType, Commit, Oid, Repository, RepositoryState, RevparseMode, Sort};
+use inquire::Confirm;
+
+// Remote branches shared with others, besides the upstream of the current branch
+const PROTECTED_REMOTE_BRANCHES: &[&str] = &["HEAD", "main", "master"];
+
+// A commit of the range along with its regenerated message
+struct Reworded {
+    id: Oid,
+    old_message: String,
+    new_message: String,
+}
+
+// Regenerate the messages of a range of commits and rewrite them
+pub async fn execute_reword(
+    range: String,
+    context: Option<String>,
+    full_gitmoji_spec: bool,
+    skip_confirmation: bool,
+) -> Result<()> {
+    println!("{}", "OpenCommit".bright_blue().bold());
+
+    let repo = assert_git_repo()?;
+    check_clean_state(&repo)?;
+    let commits = commits_in_range(&repo, &range)?;
+
+    if commits.is_empty() {
+        println!("{}", format!("No commits in {}", range).yellow());
+        return Ok(());
+    }
+
+    check_not_published(&repo, &commits)?;
+
+    let config = Config::load()?;
+    config.engine_ready()?;
+
+    let engine = get_commit_engine(&config)?;
+    let prompt = get_main_commit_prompt(full_gitmoji_spec, context.unwrap_or_default()).await?;
+    let commitlint_rules = load_commitlint_rules(&config).await?;
+
+    let spinner = new_spinner(format!("Rewording {} commits", commits.len()));
+
+    let mut reworded = Vec::with_capacity(commits.len());
+
+    for (number, id) in commits.iter().enumerate() {
+        let commit = repo.find_commit(*id)?;
+        let old_message = String::from_utf8_lossy(commit.message_bytes()).to_string();
+        let diff = get_commit_diff(&repo, &commit)?;
+
+        // Nothing to describe in empty commits, keep what they say
+        if diff.trim().is_empty() {
+            reworded.push(Reworded { id: *id, new_message: old_message.clone(), old_message });
+            continue;
+        }
+
+        spinner.set_message(format!("Writing commit message {} of {}", number + 1, commits.len()));
+        let mut new_message = match engine.generate_commit_message(prompt.clone(), &diff).await {
+            Ok(message) => message,
+            Err(e) => {
+                spinner.finish_and_clear();
+                return Err(e);
+            }
+        };
+
+        if let Some(rules) = &commitlint_rules {
+            new_message = enforce_commitlint_rules(engine.as_ref(), rules, &prompt, &diff, &new_message).await?;
+        }
+
+        reworded.push(Reworded { id: *id, old_message, new_message });
+    }
+
+    spinner.finish_and_clear();
+    print_review(&reworded);
+
+    let signed = commits.iter().filter(|id| repo.extract_signature(id, None).is_ok()).count();
+    if signed > 0 {
+        println!("{}", format!("{} of the commits are signed, the rewritten ones won't be", signed).yellow());
+    }
+
+    let confirmed = if skip_confirmation {
+        true
+    } else {
+        match Confirm::new(&format!("Rewrite {} commits with the new messages?", reworded.len()))
+            .with_default(false)
+            .prompt()
+        {
+            Ok(confirmed) => confirmed,
+            Err(_) => return Err(Error::UserCancelled),
+        }
+    };
+
+    if !confirmed {
+        println!("{}", "Reword aborted, the history is untouched".yellow());
+        return Ok(());
+    }
+
+    let new_head = rewrite(&repo, &reworded)?;
+    println!("{} Rewrote {} commits, HEAD is now at {}", "✓".green(), reworded.len(), short_id(new_head));
+
+    Ok(())
+}
+
+// Moving HEAD during a rebase, merge, cherry-pick or bisect would break the operation in progress
+fn check_clean_state(repo: &Repository) -> Result<()> {
+    match repo.state() {
+        RepositoryState::Clean => Ok(()),
+        state => Err(Error::RewriteError(format!(
+            "the repository is in the middle of an operation ({:?}), finish or abort it first",
+            state
+        ))),
+    }
+}
+
+// Commits of the range from oldest to newest, a single revision means everything after it
+fn commits_in_range(repo: &Repository, range: &str) -> Result<Vec<Oid>> {
+    let head = repo.head()?.peel_to_commit()?.id();
+    let spec = repo.revparse(range)?;
+
+    let (from, to) = if spec.mode().contains(RevparseMode::RANGE) {
+        (spec.from(), spec.to())
+    } else {
+        (spec.from(), None)
+    };
+
+    // Commits after the end of the range would keep pointing at the old history
+    let to = match to {
+        Some(to) => to.peel_to_commit()?.id(),
+        None => head,
+    };
+    if to != head {
+        return Err(Error::RewriteError(format!(
+            "{} does not end at HEAD, only the commits leading to HEAD can be reworded",
+            range
+        )));
+    }
+
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    walk.push(head)?;
+    if let Some(from) = from {
+        walk.hide(from.peel_to_commit()?.id())?;
+    }
+
+    Ok(walk.collect::<std::result::Result<Vec<_>, _>>()?)
+}
+
+// Refuse to rewrite commits that others may already have built on
+fn check_not_published(repo: &Repository, commits: &[Oid]) -> Result<()> {
+    for (name, tip) in protected_tips(repo)? {
+        for &id in commits {
+            if id == tip || repo.graph_descendant_of(tip, id)? {
+                return Err(Error::RewriteError(format!(
+                    "commit {} is already on {}",
+                    short_id(id),
+                    name
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// The upstream of the current branch and the default branches of the remotes
+fn protected_tips(repo: &Repository) -> Result<Vec<(String, Oid)>> {
+    let mut tips = Vec::new();
+
+    let head = repo.head()?;
+    if let (true, Some(name)) = (head.is_branch(), head.shorthand()) {
+        if let Ok(upstream) = repo.find_branch(name, BranchType::Local)?.upstream() {
+            if let (Some(name), Some(tip)) = (upstream.name()?, upstream.get().target()) {
+                tips.push((name.to_string(), tip));
+            }
+        }
+    }
+
+    for remote in repo.remotes()?.iter().flatten() {
+        for branch in PROTECTED_REMOTE_BRANCHES {
+            let tip = repo.find_reference(&format!("refs/remotes/{}/{}", remote, branch))
+                .and_then(|reference| reference.resolve())
+                .ok()
+                .and_then(|reference| reference.target());
+
+            if let Some(tip) = tip {
+                tips.push((format!("{}/{}", remote, branch), tip));
+            }
+        }
+    }
+
+    Ok(tips)
+}
+
+// Old and new messages next to each other, as wide as the terminal allows
+fn print_review(reworded: &[Reworded]) {
+    let width = Term::stdout().size_checked().map_or(100, |(_, columns)| columns as usize);
+    let column = width.saturating_sub(3) / 2;
+
+    for item in reworded {
+        println!("\n{}", short_id(item.id).yellow());
+        println!("{} {} {}", pad_str("Before", column, Alignment::Left, None).bright_black(), "│".bright_black(), "After".bright_black());
+
+        let old_lines: Vec<&str> = item.old_message.trim_end().lines().collect();
+        let new_lines: Vec<&str> = item.new_message.trim_end().lines().collect();
+
+        for i in 0..old_lines.len().max(new_lines.len()) {
+            let old_line = old_lines.get(i).copied().unwrap_or_default();
+            let new_line = new_lines.get(i).copied().unwrap_or_default();
+
+            println!(
+                "{} {} {}",
+                pad_str(&truncate_str(old_line, column, "…"), column, Alignment::Left, None).red(),
+                "│".bright_black(),
+                truncate_str(new_line, column, "…").green()
+            );
+        }
+    }
+    println!();
+}
+
+// Recreate the commits with their new messages, keeping authors, committers, dates and trees
+fn rewrite(repo: &Repository, reworded: &[Reworded]) -> Result<Oid> {
+    let mut rewritten: HashMap<Oid, Oid> = HashMap::new();
+    let mut new_head = None;
+
+    for item in reworded {
+        let commit = repo.find_commit(item.id)?;
+
+        let parents = commit.parent_ids()
+            .map(|id| repo.find_commit(rewritten.get(&id).copied().unwrap_or(id)))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let parents: Vec<&Commit> = parents.iter().collect();
+
+        let id = repo.commit(None, &commit.author(), &commit.committer(), &item.new_message, &commit.tree()?, &parents)?;
+        rewritten.insert(item.id, id);
+        new_head = Some(id);
+    }
+
+    // The range ends at HEAD, so the last commit rewritten is the new tip
+    let Some(new_head) = new_head else {
+        return Err(Error::RewriteError("no commits to rewrite".to_string()));
+    };
+
+    repo.head()?.set_target(new_head, &format!("rac reword: rewrote {} commits", reworded.len()))?;
+
+    Ok(new_head)
+}
+
+fn short_id(id: Oid) -> String {
+    id.to_string()[..7].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+    use std::fs;
+    use tempfile::TempDir;
+
+    // A fresh repository with a line of empty commits, oldest first, removed with the returned directory
+    fn repository(count: usize) -> (TempDir, Repository, Vec<Oid>) {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let tree_id = repo.treebuilder(None).unwrap().write().unwrap();
+        let mut commits = Vec::new();
+        {
+            let tree = repo.find_tree(tree_id).unwrap();
+            for number in 0..count {
+                let parent = commits.last().map(|id| repo.find_commit(*id).unwrap());
+                let parents: Vec<&Commit> = parent.iter().collect();
+                let id = repo.commit(Some("HEAD"), &signature, &signature, &format!("commit {}", number), &tree, &parents).unwrap();
+                commits.push(id);
+            }
+        }
+
+        (dir, repo, commits)
+    }
+
+    #[test]
+    fn ranges_end_at_head() {
+        let (_dir, repo, commits) = repository(4);
+
+        assert_eq!(commits_in_range(&repo, "HEAD~2").unwrap(), commits[2..]);
+        assert_eq!(commits_in_range(&repo, "HEAD~3..HEAD").unwrap(), commits[1..]);
+        assert!(commits_in_range(&repo, "HEAD~3..HEAD~1").is_err());
+    }
+
+    #[test]
+    fn published_commits_are_refused() {
+        let (_dir, repo, commits) = repository(3);
+        repo.remote("origin", "https://example.com/repo.git").unwrap();
+        repo.reference("refs/remotes/origin/main", commits[1], false, "test").unwrap();
+
+        assert!(check_not_published(&repo, &commits[1..]).is_err());
+        assert!(check_not_published(&repo, &commits[2..]).is_ok());
+    }
+
+    #[test]
+    fn operations_in_progress_are_refused() {
+        let (_dir, repo, commits) = repository(1);
+        assert!(check_clean_state(&repo).is_ok());
+
+        fs::write(repo.path().join("MERGE_HEAD"), format!("{}\n", commits[0])).unwrap();
+        assert!(check_clean_state(&repo).is_err());
+    }
+}
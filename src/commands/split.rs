@@ -0,0 +1,235 @@
+use crate::error::{Error, Result};
+use crate::engine::{get_engine, get_commit_engine};
+use crate::engine::engine::{AiEngine, Message};
+use crate::prompts::get_main_commit_prompt;
+use crate::utils::token_count::token_count;
+use crate::utils::git::{assert_git_repo, get_staged_diff, get_diff_hunks, apply_hunks_to_tree, head_tree, git_commit, Hunk};
+use crate::commands::commitlint::{enforce_commitlint_rules, load_commitlint_rules};
+use crate::commands::config::Config;
+use crate::utils::spinner::new_spinner;
+
+use colored::Colorize;
+use git2::{Diff, Repository};
+use inquire::Confirm;
+use log::debug;
+use serde::Deserialize;
+
+// Lines of each hunk shown to the model when grouping, the rest is left out
+const MAX_EXCERPT_LINES: usize = 40;
+
+// Instructions for grouping the staged hunks into commits
+const SPLIT_INSTRUCTIONS: &str =
+"You are to act as an author of git commits. \
+I'll send you the hunks of the staged changes of a repository, each preceded by its number in square brackets. \
+Group them into as few commits as make sense, so that each commit holds one coherent change such as a single bug fix, refactor or feature. \
+Hunks that depend on each other belong in the same commit. Order the commits so that each one builds on the previous ones. \
+Reply with JSON only, in this format: {\"commits\": [{\"summary\": \"<what the commit does>\", \"hunks\": [1, 2]}]}. \
+Every hunk number must appear in exactly one commit.";
+
+#[derive(Debug, Deserialize)]
+struct ProposedSplit {
+    commits: Vec<ProposedCommit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProposedCommit {
+    #[serde(default)]
+    summary: String,
+    hunks: Vec<usize>,
+}
+
+// A commit of the split, with the indices of the staged hunks it takes
+struct SplitCommit {
+    hunks: Vec<usize>,
+    message: String,
+}
+
+// Split the staged changes into several commits, one per logical change
+pub async fn execute_split(
+    context: Option<String>,
+    full_gitmoji_spec: bool,
+    skip_confirmation: bool,
+) -> Result<()> {
+    println!("{}", "OpenCommit".bright_blue().bold());
+
+    let repo = assert_git_repo()?;
+    let diff = get_staged_diff(&repo)?;
+    let hunks = get_diff_hunks(&diff)?;
+
+    if hunks.is_empty() {
+        println!("{}", "No changes are staged, stage the changes to split first".yellow());
+        return Err(Error::NoStagedFiles);
+    }
+
+    let config = Config::load()?;
+    config.engine_ready()?;
+
+    // The grouping answer is JSON, it can't be split into chunks and merged like a commit message
+    let listing = hunk_listing(&hunks);
+    let listing_tokens = token_count(SPLIT_INSTRUCTIONS) + token_count(&listing);
+    if hunks.len() > 1 && listing_tokens > config.tokens_max_input.saturating_sub(config.tokens_max_output) {
+        println!(
+            "{}",
+            format!("Too many staged hunks to group in one request ({} tokens), stage fewer changes and split them in several runs", listing_tokens).yellow()
+        );
+        return Err(Error::TooManyTokens(listing_tokens));
+    }
+
+    let progress = new_spinner(format!("Grouping {} staged hunks", hunks.len()));
+    let groups = propose_groups(get_engine(&config)?.as_ref(), hunks.len(), &listing).await;
+    progress.finish_and_clear();
+    let groups = groups?;
+
+    let engine = get_commit_engine(&config)?;
+
+    let prompt = get_main_commit_prompt(full_gitmoji_spec, context.unwrap_or_default()).await?;
+    let commitlint_rules = load_commitlint_rules(&config).await?;
+
+    // Write a message for each group from its own part of the diff
+    let total = groups.len();
+    let mut commits = Vec::with_capacity(total);
+    for (number, group) in groups.into_iter().enumerate() {
+        let group_diff: String = group.iter().map(|&i| hunks[i].to_diff()).collect();
+
+        let progress = new_spinner(format!("Writing commit message {} of {}", number + 1, total));
+        let generated = engine.generate_commit_message(prompt.clone(), &group_diff).await;
+        progress.finish_and_clear();
+        let mut message = generated?;
+
+        if let Some(rules) = &commitlint_rules {
+            message = enforce_commitlint_rules(engine.as_ref(), rules, &prompt, &group_diff, &message).await?;
+        }
+
+        commits.push(SplitCommit { hunks: group, message });
+    }
+
+    // Show the proposed commits with the changes going into each
+    for (number, commit) in commits.iter().enumerate() {
+        println!("\n{}", format!("Commit {} of {}:", number + 1, commits.len()).green());
+        println!("{}", "——————————————————".bright_black());
+        println!("{}", commit.message.trim_end());
+        println!("{}", "——————————————————".bright_black());
+
+        for &i in &commit.hunks {
+            println!("  {} {}", hunks[i].path, hunks[i].header.trim_end().bright_black());
+        }
+    }
+    println!();
+
+    let confirmed = if skip_confirmation {
+        true
+    } else {
+        match Confirm::new(&format!("Create these {} commits?", commits.len()))
+            .with_default(true)
+            .prompt()
+        {
+            Ok(confirmed) => confirmed,
+            Err(_) => return Err(Error::UserCancelled),
+        }
+    };
+
+    if !confirmed {
+        println!("{}", "Split aborted, the staged changes are untouched".yellow());
+        return Ok(());
+    }
+
+    commit_groups(&repo, &diff, &hunks, &commits)
+}
+
+// The numbered hunks as sent to the model for grouping
+fn hunk_listing(hunks: &[Hunk]) -> String {
+    hunks.iter()
+        .enumerate()
+        .map(|(i, hunk)| format!("[{}] {}\n{}{}", i + 1, hunk.path, hunk.header, excerpt(&hunk.body)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Ask the model which of the listed hunks belong together, as groups of hunk indices
+async fn propose_groups(engine: &dyn AiEngine, hunk_count: usize, listing: &str) -> Result<Vec<Vec<usize>>> {
+    if hunk_count == 1 {
+        return Ok(vec![vec![0]]);
+    }
+
+    let response = engine.generate_commit_message(vec![Message::system(SPLIT_INSTRUCTIONS)], listing).await?;
+
+    // Extract JSON from response if needed
+    let json = match (response.find('{'), response.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &response[start..=end],
+        _ => response.as_str(),
+    };
+
+    let proposed: ProposedSplit = serde_json::from_str(json).map_err(|e| Error::AiProviderError(
+        format!("Could not read the proposed commits: {}", e)
+    ))?;
+
+    let mut assigned = vec![false; hunk_count];
+    let mut groups = Vec::new();
+
+    for commit in proposed.commits {
+        debug!("Proposed commit for hunks {:?}: {}", commit.hunks, commit.summary);
+
+        // Numbers are 1-based, hunks the model mentions twice stay in their first commit
+        let mut group: Vec<usize> = commit.hunks.into_iter()
+            .filter_map(|number| number.checked_sub(1))
+            .filter(|&i| i < hunk_count && !std::mem::replace(&mut assigned[i], true))
+            .collect();
+        group.sort_unstable();
+
+        if !group.is_empty() {
+            groups.push(group);
+        }
+    }
+
+    // Hunks the model left out still have to be committed
+    let rest: Vec<usize> = (0..hunk_count).filter(|&i| !assigned[i]).collect();
+    if !rest.is_empty() {
+        groups.push(rest);
+    }
+
+    Ok(groups)
+}
+
+fn excerpt(body: &str) -> String {
+    let lines: Vec<&str> = body.lines().collect();
+    if lines.len() <= MAX_EXCERPT_LINES {
+        return body.to_string();
+    }
+
+    format!("{}\n... {} more lines\n", lines[..MAX_EXCERPT_LINES].join("\n"), lines.len() - MAX_EXCERPT_LINES)
+}
+
+// Stage and commit each group on top of the previous ones, the working tree is never touched
+fn commit_groups(repo: &Repository, diff: &Diff, hunks: &[Hunk], commits: &[SplitCommit]) -> Result<()> {
+    let mut index = repo.index()?;
+    let staged_tree = index.write_tree()?;
+
+    // Hunks only apply to the tree they were computed against, so each commit gets the base plus every group so far
+    let base_tree = match head_tree(repo)? {
+        Some(tree) => tree,
+        None => repo.find_tree(repo.treebuilder(None)?.write()?)?,
+    };
+
+    let mut selected: Vec<&Hunk> = Vec::new();
+
+    for commit in commits {
+        selected.extend(commit.hunks.iter().map(|&i| &hunks[i]));
+
+        let result = apply_hunks_to_tree(repo, &base_tree, diff, &selected).and_then(|tree| {
+            index.read_tree(&tree)?;
+            index.write()?;
+            git_commit(&commit.message)
+        });
+
+        if let Err(e) = result {
+            // Whatever wasn't committed stays staged, as before the split
+            index.read_tree(&repo.find_tree(staged_tree)?)?;
+            index.write()?;
+            return Err(e);
+        }
+
+        println!("{} {}", "✓".green(), commit.message.lines().next().unwrap_or_default());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use crate::error::{Error, Result};
+use crate::engine::get_commit_engine;
+use crate::prompts::get_main_commit_prompt;
+use crate::utils::git::{assert_git_repo, get_default_branch, get_merge_base, get_commits_since, get_tree_diff, git_commit};
+use crate::commands::commitlint::{enforce_commitlint_rules, load_commitlint_rules};
+use crate::commands::config::Config;
+use crate::utils::spinner::new_spinner;
+
+use colored::Colorize;
+use git2::{Commit, Repository, ResetType};
+use inquire::Confirm;
+
+// Generate one message summing up the current branch, optionally squashing the branch with it
+pub async fn execute_squash_message(
+    base: Option<String>,
+    apply: bool,
+    context: Option<String>,
+    full_gitmoji_spec: bool,
+    skip_confirmation: bool,
+) -> Result<()> {
+    println!("{}", "OpenCommit".bright_blue().bold());
+
+    let repo = assert_git_repo()?;
+    let base = match base {
+        Some(base) => base,
+        None => get_default_branch(&repo)?,
+    };
+    let head = repo.head()?.peel_to_commit()?;
+    let merge_base = get_merge_base(&repo, &base)?;
+
+    let subjects: Vec<String> = get_commits_since(&repo, &merge_base)?
+        .iter()
+        .map(|commit| commit.summary().unwrap_or_default().to_string())
+        .collect();
+    if subjects.is_empty() {
+        println!("{}", format!("No commits on this branch since {}", base).yellow());
+        return Ok(());
+    }
+
+    let diff = get_tree_diff(&repo, Some(&merge_base.tree()?), &head.tree()?)?;
+    if diff.trim().is_empty() {
+        println!("{}", format!("The branch makes no changes to {}", base).yellow());
+        return Ok(());
+    }
+
+    let config = Config::load()?;
+    config.engine_ready()?;
+
+    // The existing subjects tell the model what the branch was about
+    let mut squash_context = format!(
+        "These changes squash the {} commits of a branch into one. Their subjects, oldest first:\n{}\n\
+        Write a single commit message covering all of them: a summary line, a blank line, then a body listing the main changes as bullet points.",
+        subjects.len(),
+        subjects.iter().map(|s| format!("- {}", s)).collect::<Vec<_>>().join("\n")
+    );
+    if let Some(context) = context {
+        squash_context.push_str(&format!("\n{}", context));
+    }
+
+    let messages = get_main_commit_prompt(full_gitmoji_spec, squash_context).await?;
+    let engine = get_commit_engine(&config)?;
+
+    let spinner = new_spinner(format!("Summing up {} commits since {}", subjects.len(), base));
+
+    let generated = engine.generate_commit_message(messages.clone(), &diff).await;
+    spinner.finish_and_clear();
+    let mut commit_message = generated?;
+
+    if let Some(rules) = load_commitlint_rules(&config).await? {
+        commit_message = enforce_commitlint_rules(engine.as_ref(), &rules, &messages, &diff, &commit_message).await?;
+    }
+
+    println!("\n{}", format!("Squash commit message for {} commits:", subjects.len()).green());
+    println!("{}", "——————————————————".bright_black());
+    println!("{}", commit_message.trim_end());
+    println!("{}", "——————————————————".bright_black());
+
+    if !apply {
+        return Ok(());
+    }
+
+    // The squash commit is made from the index, anything staged would end up in it
+    if repo.diff_tree_to_index(Some(&head.tree()?), None, None)?.deltas().len() > 0 {
+        return Err(Error::RewriteError(
+            "there are staged changes, commit or unstage them before squashing".to_string()
+        ));
+    }
+
+    let confirmed = if skip_confirmation {
+        true
+    } else {
+        match Confirm::new(&format!("Squash the {} commits since {} into one?", subjects.len(), base))
+            .with_default(false)
+            .prompt()
+        {
+            Ok(confirmed) => confirmed,
+            Err(_) => return Err(Error::UserCancelled),
+        }
+    };
+
+    if !confirmed {
+        println!("{}", "Squash aborted, the branch is untouched".yellow());
+        return Ok(());
+    }
+
+    squash(&repo, &merge_base, &head, &commit_message)?;
+    println!("{} Squashed {} commits into one", "✓".green(), subjects.len());
+
+    Ok(())
+}
+
+// Move the branch back to the merge base keeping its tree staged, then commit it at once
+fn squash(repo: &Repository, merge_base: &Commit, head: &Commit, message: &str) -> Result<()> {
+    repo.reset(merge_base.as_object(), ResetType::Soft, None)?;
+
+    if let Err(e) = git_commit(message) {
+        // Put the branch back the way it was
+        repo.reset(head.as_object(), ResetType::Soft, None)?;
+        return Err(e);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,275 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Serialize, Deserialize};
+use std::time::Duration;
+use crate::error::{Error, Result};
+use crate::engine::engine::{AiEngine, EngineConfig, Message};
+use crate::engine::http;
+use crate::utils::token_count::token_count;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+// Sampling the API uses for parameters left out of the request
+const DEFAULT_TEMPERATURE: f32 = 1.0;
+const DEFAULT_TOP_P: f32 = 1.0;
+
+#[derive(Debug, Clone)]
+pub struct AnthropicEngine {
+    config: EngineConfig,
+    client: Client,
+}
+
+#[derive(Debug, Serialize)]
+struct AnthropicMessagesRequest {
+    model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    max_tokens: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AnthropicMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicMessagesResponse {
+    content: Vec<AnthropicContentBlock>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicContentBlock {
+    #[serde(rename = "type")]
+    block_type: String,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicErrorResponse {
+    error: AnthropicErrorDetail,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicErrorDetail {
+    #[serde(rename = "type")]
+    error_type: String,
+    message: String,
+}
+
+impl AnthropicEngine {
+    pub fn new(config: EngineConfig) -> Self {
+        let client = http::build_client(&config, Duration::from_secs(120));
+
+        Self {
+            config,
+            client,
+        }
+    }
+
+    fn get_base_url(&self) -> String {
+        self.config.base_url.clone()
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| "https://api.anthropic.com/v1".to_string())
+    }
+}
+
+#[async_trait]
+impl AiEngine for AnthropicEngine {
+    async fn generate_commit_message(&self, messages: Vec<Message>, diff: &str) -> Result<String> {
+        // Anthropic takes the system prompt as a separate field
+        let system = messages.iter()
+            .filter(|m| m.role == "system")
+            .map(|m| m.content.clone())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // Keep the example exchange, the API requires user/assistant turns to alternate
+        let mut anthropic_messages: Vec<AnthropicMessage> = messages.iter()
+            .filter(|m| m.role != "system")
+            .map(|m| AnthropicMessage {
+                role: m.role.clone(),
+                content: m.content.clone(),
+            })
+            .collect();
+
+        // Add user message with diff
+        anthropic_messages.push(AnthropicMessage {
+            role: "user".to_string(),
+            content: diff.to_string(),
+        });
+
+        // Calculate token count
+        let request_tokens = token_count(&system) + anthropic_messages.iter()
+            .map(|msg| token_count(&msg.content) + 4)
+            .sum::<usize>();
+
+        if request_tokens > self.config.max_tokens_input - self.config.max_tokens_output {
+            return Err(Error::TooManyTokens(request_tokens));
+        }
+
+        // Current models reject requests setting both, top_p only goes out when it is the one that was changed
+        let sampling = self.config.sampling;
+        let (temperature, top_p) = if sampling.temperature == DEFAULT_TEMPERATURE && sampling.top_p != DEFAULT_TOP_P {
+            (None, Some(sampling.top_p))
+        } else {
+            (Some(sampling.temperature), None)
+        };
+
+        // Prepare request
+        let request = AnthropicMessagesRequest {
+            model: self.config.model.clone(),
+            system: if system.is_empty() { None } else { Some(system) },
+            messages: anthropic_messages,
+            temperature,
+            top_p,
+            max_tokens: self.config.max_tokens_output,
+        };
+
+        // Send request
+        let request_builder = self.client.post(format!("{}/messages", self.get_base_url()))
+            .header("Content-Type", "application/json")
+            .header("x-api-key", &self.config.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(&request);
+        let response = http::send_with_retries(request_builder, self.config.max_retries).await?;
+
+        // Handle errors
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await?;
+            let message = match serde_json::from_str::<AnthropicErrorResponse>(&error_text) {
+                Ok(error) => format!("{} ({})", error.error.message, error.error.error_type),
+                Err(_) => error_text,
+            };
+            return Err(Error::ApiError { provider: "Anthropic".to_string(), status: status.as_u16(), message });
+        }
+
+        // Parse response
+        let response: AnthropicMessagesResponse = response.json().await?;
+
+        // Get message content
+        let message = response.content.iter()
+            .filter(|block| block.block_type == "text")
+            .map(|block| block.text.as_str())
+            .collect::<String>();
+
+        if message.is_empty() {
+            return Err(Error::EmptyCommitMessage);
+        }
+
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::engine::Sampling;
+    use crate::engine::http::mock::{request_body, serve_once};
+
+    fn engine(base_url: String, sampling: Sampling) -> AnthropicEngine {
+        AnthropicEngine::new(EngineConfig {
+            api_key: "sk-ant-test".to_string(),
+            model: "claude-3-5-haiku-latest".to_string(),
+            max_tokens_output: 500,
+            max_tokens_input: 4096,
+            // A trailing slash, as often pasted into OCO_API_URL, must not end up in the path
+            base_url: Some(format!("{}/v1/", base_url)),
+            azure_deployment: None,
+            azure_api_version: None,
+            flowise_flow_id: None,
+            request_timeout: Some(Duration::from_secs(5)),
+            max_retries: 0,
+            sampling,
+        })
+    }
+
+    fn prompt() -> Vec<Message> {
+        vec![
+            Message::system("You write commit messages."),
+            Message::user("example diff"),
+            Message::assistant("fix: example"),
+        ]
+    }
+
+    #[tokio::test]
+    async fn sends_messages_and_reads_the_text() {
+        let (url, server) = serve_once(
+            "200 OK",
+            r#"{"content":[{"type":"text","text":"feat: add "},{"type":"text","text":"parser"}]}"#,
+        ).await;
+
+        let message = engine(url, Sampling::DETERMINISTIC).generate_commit_message(prompt(), "the diff").await.unwrap();
+        assert_eq!(message, "feat: add parser");
+
+        let request = server.await.unwrap();
+        let head = request.to_lowercase();
+        assert!(head.starts_with("post /v1/messages "));
+        assert!(head.contains("x-api-key: sk-ant-test"));
+        assert!(head.contains(&format!("anthropic-version: {}", ANTHROPIC_VERSION)));
+
+        // The system prompt goes in its own field, the turns keep alternating
+        let body = request_body(&request);
+        assert_eq!(body["system"], "You write commit messages.");
+        let roles: Vec<&str> = body["messages"].as_array().unwrap().iter().map(|m| m["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, ["user", "assistant", "user"]);
+        assert_eq!(body["messages"][2]["content"], "the diff");
+        assert_eq!(body["max_tokens"], 500);
+
+        // Only one of temperature and top_p
+        assert_eq!(body["temperature"], 0.0);
+        assert!(body.get("top_p").is_none());
+    }
+
+    #[tokio::test]
+    async fn sends_top_p_only_when_temperature_is_left_alone() {
+        let (url, server) = serve_once("200 OK", r#"{"content":[{"type":"text","text":"fix: x"}]}"#).await;
+        let sampling = Sampling { temperature: DEFAULT_TEMPERATURE, top_p: 0.5 };
+
+        engine(url, sampling).generate_commit_message(prompt(), "the diff").await.unwrap();
+
+        let body = request_body(&server.await.unwrap());
+        assert!(body.get("temperature").is_none());
+        assert_eq!(body["top_p"], 0.5);
+    }
+
+    #[tokio::test]
+    async fn maps_authentication_errors() {
+        let (url, server) = serve_once(
+            "401 Unauthorized",
+            r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#,
+        ).await;
+
+        let error = engine(url, Sampling::DETERMINISTIC).generate_commit_message(prompt(), "the diff").await.unwrap_err();
+        server.await.unwrap();
+
+        match error {
+            Error::ApiError { provider, status, message } => {
+                assert_eq!(provider, "Anthropic");
+                assert_eq!(status, 401);
+                assert_eq!(message, "invalid x-api-key (authentication_error)");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn maps_rate_limits() {
+        let (url, server) = serve_once(
+            "429 Too Many Requests",
+            r#"{"type":"error","error":{"type":"rate_limit_error","message":"slow down"}}"#,
+        ).await;
+
+        let error = engine(url, Sampling::DETERMINISTIC).generate_commit_message(prompt(), "the diff").await.unwrap_err();
+        server.await.unwrap();
+
+        assert!(matches!(error, Error::ApiError { status: 429, .. }), "unexpected error: {:?}", error);
+    }
+}
@@ -0,0 +1,133 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use std::time::Duration;
+use crate::error::{Error, Result};
+use crate::engine::engine::{unique, AiEngine, EngineConfig, Message, TokenCallback};
+use crate::engine::http;
+use crate::engine::openai::{
+    build_chat_completion_request, parse_chat_completion_choices, parse_chat_completion_response,
+    read_chat_completion_stream, OpenAiChatCompletionRequest,
+};
+
+const DEFAULT_API_VERSION: &str = "2024-06-01";
+
+#[derive(Debug, Clone)]
+pub struct AzureEngine {
+    config: EngineConfig,
+    client: Client,
+}
+
+impl AzureEngine {
+    pub fn new(config: EngineConfig) -> Self {
+        let client = http::build_client(&config, Duration::from_secs(120));
+
+        Self {
+            config,
+            client,
+        }
+    }
+
+    // Azure routes by deployment rather than by model name
+    fn get_completions_url(&self) -> Result<String> {
+        let endpoint = self.config.base_url.as_deref()
+            .ok_or_else(|| Error::InvalidConfiguration(
+                "OCO_API_URL must be set to your Azure OpenAI endpoint".to_string()
+            ))?
+            .trim_end_matches('/');
+
+        // Fall back to the model name, which is how most deployments are named
+        let deployment = self.config.azure_deployment.as_deref()
+            .unwrap_or(&self.config.model);
+
+        let api_version = self.config.azure_api_version.as_deref()
+            .unwrap_or(DEFAULT_API_VERSION);
+
+        Ok(format!(
+            "{}/openai/deployments/{}/chat/completions?api-version={}",
+            endpoint, deployment, api_version
+        ))
+    }
+
+    // Send a chat completion request to the configured deployment
+    async fn send(&self, request: &OpenAiChatCompletionRequest) -> Result<reqwest::Response> {
+        let request_builder = self.client.post(self.get_completions_url()?)
+            .header("Content-Type", "application/json")
+            .header("api-key", &self.config.api_key)
+            .json(request);
+
+        http::send_with_retries(request_builder, self.config.max_retries).await
+    }
+}
+
+#[async_trait]
+impl AiEngine for AzureEngine {
+    async fn generate_commit_message(&self, messages: Vec<Message>, diff: &str) -> Result<String> {
+        // Prepare request
+        let request = build_chat_completion_request(&self.config, &messages, diff)?;
+
+        // Send request
+        let response = self.send(&request).await?;
+
+        parse_chat_completion_response(response, "Azure OpenAI").await
+    }
+
+    async fn generate_commit_message_stream(&self, messages: Vec<Message>, diff: &str, on_token: TokenCallback<'_>) -> Result<String> {
+        let mut request = build_chat_completion_request(&self.config, &messages, diff)?;
+        request.stream = true;
+
+        let response = self.send(&request).await?;
+
+        read_chat_completion_stream(response, "Azure OpenAI", on_token).await
+    }
+
+    async fn generate_commit_messages(&self, messages: Vec<Message>, diff: &str, n: usize) -> Result<Vec<String>> {
+        let mut request = build_chat_completion_request(&self.config, &messages, diff)?;
+        request.n = Some(n);
+
+        let response = self.send(&request).await?;
+
+        Ok(unique(parse_chat_completion_choices(response, "Azure OpenAI").await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::engine::Sampling;
+    use crate::engine::http::mock::{request_body, serve_once};
+
+    #[tokio::test]
+    async fn sends_to_the_deployment_with_the_api_key_header() {
+        let (url, server) = serve_once(
+            "200 OK",
+            r#"{"choices":[{"message":{"role":"assistant","content":"fix: retry requests"}}]}"#,
+        ).await;
+
+        let engine = AzureEngine::new(EngineConfig {
+            api_key: "azure-key".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            max_tokens_output: 500,
+            max_tokens_input: 4096,
+            base_url: Some(format!("{}/", url)),
+            azure_deployment: Some("commits".to_string()),
+            azure_api_version: Some("2024-10-21".to_string()),
+            flowise_flow_id: None,
+            request_timeout: Some(Duration::from_secs(5)),
+            max_retries: 0,
+            sampling: Sampling::DETERMINISTIC,
+        });
+
+        let message = engine.generate_commit_message(vec![Message::system("You write commit messages.")], "the diff").await.unwrap();
+        assert_eq!(message, "fix: retry requests");
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /openai/deployments/commits/chat/completions?api-version=2024-10-21 "));
+
+        let head = request.to_lowercase();
+        assert!(head.contains("api-key: azure-key"));
+        assert!(!head.contains("authorization:"));
+
+        let body = request_body(&request);
+        assert_eq!(body["messages"][1]["content"], "the diff");
+    }
+}
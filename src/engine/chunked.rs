@@ -0,0 +1,312 @@
+use async_trait::async_trait;
+use log::debug;
+use crate::error::{Error, Result};
+use crate::engine::engine::{collect_candidates, AiEngine, Message, TokenCallback};
+use crate::utils::token_count::token_count;
+
+// Per-message overhead the engines add when counting tokens
+const MESSAGE_TOKEN_OVERHEAD: usize = 4;
+
+// Instruction used to combine the partial commit messages of a large diff
+const MERGE_INSTRUCTION: &str =
+"The diff was too large to process at once, so it was split into parts and a commit message was written for each part. \
+Below are those partial commit messages, separated by lines of dashes. \
+Combine them into a single commit message that describes the whole change, following the same conventions. \
+Do not mention that the change was split into parts.";
+
+// Separator between the merge instruction and the partial commit messages
+const MERGE_SEPARATOR: &str = "\n\n";
+
+// Separator between partial commit messages in the merge request
+const PARTIAL_SEPARATOR: &str = "\n----------\n";
+
+// Wraps any engine and splits diffs that do not fit into its context window
+pub struct ChunkedEngine {
+    inner: Box<dyn AiEngine>,
+    max_tokens_input: usize,
+    max_tokens_output: usize,
+}
+
+impl ChunkedEngine {
+    pub fn new(inner: Box<dyn AiEngine>, max_tokens_input: usize, max_tokens_output: usize) -> Self {
+        Self {
+            inner,
+            max_tokens_input,
+            max_tokens_output,
+        }
+    }
+
+    // Tokens left for the diff once the prompt messages are accounted for
+    fn diff_budget(&self, messages: &[Message]) -> Result<usize> {
+        let prompt_tokens = messages.iter()
+            .map(|m| token_count(&m.content) + MESSAGE_TOKEN_OVERHEAD)
+            .sum::<usize>() + MESSAGE_TOKEN_OVERHEAD;
+
+        self.max_tokens_input
+            .checked_sub(self.max_tokens_output)
+            .and_then(|available| available.checked_sub(prompt_tokens))
+            .filter(|budget| *budget > 0)
+            .ok_or(Error::TooManyTokens(prompt_tokens))
+    }
+
+    // Combine partial messages, merging in batches until they fit in one request
+    async fn merge_partials(&self, messages: &[Message], mut partials: Vec<String>, budget: usize, on_token: Option<TokenCallback<'_>>) -> Result<String> {
+        let batch_budget = budget.saturating_sub(token_count(&format!("{}{}", MERGE_INSTRUCTION, MERGE_SEPARATOR)));
+
+        loop {
+            let batches = pack_chunks(&partials, batch_budget, PARTIAL_SEPARATOR)?;
+
+            // Only the final merge produces text worth streaming
+            if let (1, Some(on_token)) = (batches.len(), on_token) {
+                let merge_input = format!("{}{}{}", MERGE_INSTRUCTION, MERGE_SEPARATOR, batches[0]);
+                return self.inner.generate_commit_message_stream(messages.to_vec(), &merge_input, on_token).await;
+            }
+
+            let mut merged = Vec::with_capacity(batches.len());
+
+            for batch in batches {
+                let merge_input = format!("{}{}{}", MERGE_INSTRUCTION, MERGE_SEPARATOR, batch);
+                merged.push(self.inner.generate_commit_message(messages.to_vec(), &merge_input).await?);
+            }
+
+            if merged.len() == 1 {
+                return Ok(merged.remove(0));
+            }
+
+            // A batch of one makes no progress, stop before looping forever
+            if merged.len() >= partials.len() {
+                return Err(Error::TooManyTokens(partials.iter().map(|p| token_count(p)).sum()));
+            }
+
+            partials = merged;
+        }
+    }
+
+    async fn generate(&self, messages: Vec<Message>, diff: &str, on_token: Option<TokenCallback<'_>>) -> Result<String> {
+        let budget = self.diff_budget(&messages)?;
+
+        if token_count(diff) <= budget {
+            return match on_token {
+                Some(on_token) => self.inner.generate_commit_message_stream(messages, diff, on_token).await,
+                None => self.inner.generate_commit_message(messages, diff).await,
+            };
+        }
+
+        // Map: one partial commit message per chunk of the diff
+        let partials = self.summarize_chunks(&messages, diff, budget).await?;
+
+        // Reduce: merge the partial messages into the final one
+        self.merge_partials(&messages, partials, budget, on_token).await
+    }
+
+    async fn summarize_chunks(&self, messages: &[Message], diff: &str, budget: usize) -> Result<Vec<String>> {
+        let chunks = split_diff(diff, budget)?;
+        debug!("Diff exceeds {} tokens, splitting it into {} chunks", budget, chunks.len());
+
+        let mut partials = Vec::with_capacity(chunks.len());
+        for chunk in &chunks {
+            partials.push(self.inner.generate_commit_message(messages.to_vec(), chunk).await?);
+        }
+
+        Ok(partials)
+    }
+}
+
+#[async_trait]
+impl AiEngine for ChunkedEngine {
+    async fn generate_commit_message(&self, messages: Vec<Message>, diff: &str) -> Result<String> {
+        self.generate(messages, diff, None).await
+    }
+
+    async fn generate_commit_message_stream(&self, messages: Vec<Message>, diff: &str, on_token: TokenCallback<'_>) -> Result<String> {
+        self.generate(messages, diff, Some(on_token)).await
+    }
+
+    async fn generate_commit_messages(&self, messages: Vec<Message>, diff: &str, n: usize) -> Result<Vec<String>> {
+        let budget = self.diff_budget(&messages)?;
+
+        if token_count(diff) <= budget {
+            return self.inner.generate_commit_messages(messages, diff, n).await;
+        }
+
+        // The partial messages only need to be written once, the candidates differ in how they are merged
+        let partials = self.summarize_chunks(&messages, diff, budget).await?;
+        let merges = (0..n).map(|_| self.merge_partials(&messages, partials.clone(), budget, None));
+
+        collect_candidates(futures::future::join_all(merges).await)
+    }
+}
+
+// Split a diff into chunks of at most `budget` tokens, by file first and then by hunk
+pub fn split_diff(diff: &str, budget: usize) -> Result<Vec<String>> {
+    let mut pieces = Vec::new();
+
+    for file_diff in split_by_prefix(diff, "diff --git ") {
+        if token_count(&file_diff) <= budget {
+            pieces.push(file_diff);
+            continue;
+        }
+
+        // Repeat the file header in front of every hunk so each chunk keeps its context
+        let mut hunks = split_by_prefix(&file_diff, "@@ ");
+        let header = if hunks.len() > 1 && !hunks[0].starts_with("@@ ") { hunks.remove(0) } else { String::new() };
+
+        for hunk in hunks {
+            let hunk_budget = budget.saturating_sub(token_count(&header));
+            for part in split_by_lines(&hunk, hunk_budget)? {
+                pieces.push(format!("{}{}", header, part));
+            }
+        }
+    }
+
+    pack_chunks(&pieces, budget, "")
+}
+
+// Split text into sections that each start with a line beginning with `prefix`
+pub(crate) fn split_by_prefix(text: &str, prefix: &str) -> Vec<String> {
+    let mut sections: Vec<String> = Vec::new();
+
+    for line in text.split_inclusive('\n') {
+        match sections.last_mut() {
+            Some(section) if !line.starts_with(prefix) => section.push_str(line),
+            _ => sections.push(line.to_string()),
+        }
+    }
+
+    sections
+}
+
+// Last resort for a single hunk larger than the budget
+fn split_by_lines(text: &str, budget: usize) -> Result<Vec<String>> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut current_tokens = 0;
+
+    for line in text.split_inclusive('\n') {
+        let line_tokens = token_count(line);
+        if line_tokens > budget {
+            return Err(Error::TooManyTokens(line_tokens));
+        }
+
+        if current_tokens + line_tokens > budget && !current.is_empty() {
+            parts.push(std::mem::take(&mut current));
+            current_tokens = 0;
+        }
+
+        current.push_str(line);
+        current_tokens += line_tokens;
+    }
+
+    if !current.is_empty() {
+        parts.push(current);
+    }
+
+    Ok(parts)
+}
+
+// Greedily pack pieces into as few chunks of at most `budget` tokens as possible
+fn pack_chunks(pieces: &[String], budget: usize, separator: &str) -> Result<Vec<String>> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for piece in pieces {
+        let piece_tokens = token_count(piece);
+        if piece_tokens > budget {
+            return Err(Error::TooManyTokens(piece_tokens));
+        }
+
+        let candidate = format!("{}{}{}", current, separator, piece);
+        if current.is_empty() {
+            current = piece.clone();
+        } else if token_count(&candidate) <= budget {
+            current = candidate;
+        } else {
+            chunks.push(std::mem::replace(&mut current, piece.clone()));
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Records the diffs it is sent and answers with a short message
+    struct RecordingEngine {
+        diffs: std::sync::Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl AiEngine for RecordingEngine {
+        async fn generate_commit_message(&self, _messages: Vec<Message>, diff: &str) -> Result<String> {
+            self.diffs.lock().unwrap().push(diff.to_string());
+            Ok("fix: update part".to_string())
+        }
+    }
+
+    fn file_diff(name: &str, lines: usize) -> String {
+        let mut diff = format!("diff --git a/{name} b/{name}\n--- a/{name}\n+++ b/{name}\n@@ -1,{lines} +1,{lines} @@\n");
+        for i in 0..lines {
+            diff.push_str(&format!("+let value_{} = compute({});\n", i, i));
+        }
+        diff
+    }
+
+    #[test]
+    fn packs_pieces_greedily() {
+        let pieces: Vec<String> = ["one two", "three four", "five six"].iter().map(|s| s.to_string()).collect();
+
+        assert_eq!(pack_chunks(&pieces, 100, " ").unwrap(), vec!["one two three four five six"]);
+
+        let chunks = pack_chunks(&pieces, token_count("one two three four"), " ").unwrap();
+        assert_eq!(chunks, vec!["one two three four", "five six"]);
+        assert!(chunks.iter().all(|chunk| token_count(chunk) <= token_count("one two three four")));
+
+        assert!(pack_chunks(&pieces, 0, " ").is_err());
+        assert!(pack_chunks(&[], 10, " ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn splits_diffs_by_file_then_hunk() {
+        let diff = format!("{}{}", file_diff("a.rs", 5), file_diff("b.rs", 5));
+
+        // Small files stay whole
+        assert_eq!(split_diff(&diff, token_count(&diff)).unwrap(), vec![diff.clone()]);
+        let chunks = split_diff(&diff, token_count(&file_diff("a.rs", 5)) + 1).unwrap();
+        assert_eq!(chunks, vec![file_diff("a.rs", 5), file_diff("b.rs", 5)]);
+
+        // A large hunk is split by lines, each part keeping the file header
+        let large = file_diff("c.rs", 200);
+        let budget = token_count(&large) / 4;
+        let chunks = split_diff(&large, budget).unwrap();
+        assert!(chunks.len() >= 4);
+        for chunk in &chunks {
+            assert!(chunk.starts_with("diff --git a/c.rs b/c.rs\n"));
+            assert!(token_count(chunk) <= budget);
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_stay_within_the_budget() {
+        let diffs = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let engine = ChunkedEngine::new(Box::new(RecordingEngine { diffs: diffs.clone() }), 700, 100);
+        let messages = vec![Message::system("Write a commit message.")];
+        let budget = engine.diff_budget(&messages).unwrap();
+
+        let diff: String = (0..12).map(|i| file_diff(&format!("file{}.rs", i), 20)).collect();
+        assert!(token_count(&diff) > budget);
+
+        assert_eq!(engine.generate_commit_message(messages, &diff).await.unwrap(), "fix: update part");
+
+        let diffs = diffs.lock().unwrap();
+        assert!(diffs.len() > 2);
+        assert!(diffs.iter().all(|diff| token_count(diff) <= budget));
+        assert!(diffs.last().unwrap().starts_with(MERGE_INSTRUCTION));
+    }
+}
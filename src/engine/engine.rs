@@ -1,5 +1,6 @@
 use async_trait::async_trait;
 use serde::{Serialize, Deserialize};
+use std::time::Duration;
 use crate::error::Result;
 
 // Message struct for API requests
@@ -32,6 +33,18 @@ impl Message {
     }
 }
 
+// Sampling settings, commit messages are deterministic unless alternatives are wanted
+#[derive(Debug, Clone, Copy)]
+pub struct Sampling {
+    pub temperature: f32,
+    pub top_p: f32,
+}
+
+impl Sampling {
+    pub const DETERMINISTIC: Sampling = Sampling { temperature: 0.0, top_p: 0.1 };
+    pub const VARIED: Sampling = Sampling { temperature: 0.8, top_p: 1.0 };
+}
+
 // Configuration for AI engines
 #[derive(Debug, Clone)]
 pub struct EngineConfig {
@@ -40,10 +53,69 @@ pub struct EngineConfig {
     pub max_tokens_output: usize,
     pub max_tokens_input: usize,
     pub base_url: Option<String>,
+    pub azure_deployment: Option<String>,
+    pub azure_api_version: Option<String>,
+    pub flowise_flow_id: Option<String>,
+    // Engines pick their own default when not configured
+    pub request_timeout: Option<Duration>,
+    pub max_retries: u32,
+    pub sampling: Sampling,
 }
 
+// Callback receiving each piece of a message as it is generated
+pub type TokenCallback<'a> = &'a (dyn Fn(&str) + Send + Sync);
+
 // Trait for AI engines
 #[async_trait]
 pub trait AiEngine: Send + Sync {
     async fn generate_commit_message(&self, messages: Vec<Message>, diff: &str) -> Result<String>;
-}
\ No newline at end of file
+    
+    // Generate a message while reporting partial output, engines that can't stream send it in one piece
+    async fn generate_commit_message_stream(&self, messages: Vec<Message>, diff: &str, on_token: TokenCallback<'_>) -> Result<String> {
+        let message = self.generate_commit_message(messages, diff).await?;
+        on_token(&message);
+        Ok(message)
+    }
+    
+    // Generate `n` alternative messages, engines without native support make parallel requests
+    async fn generate_commit_messages(&self, messages: Vec<Message>, diff: &str, n: usize) -> Result<Vec<String>> {
+        generate_in_parallel(self, messages, diff, n).await
+    }
+}
+
+// Make `n` requests at once
+pub async fn generate_in_parallel<E: AiEngine + ?Sized>(engine: &E, messages: Vec<Message>, diff: &str, n: usize) -> Result<Vec<String>> {
+    let requests = (0..n).map(|_| engine.generate_commit_message(messages.clone(), diff));
+    collect_candidates(futures::future::join_all(requests).await)
+}
+
+// Keep the messages that were generated, failing only when none were
+pub fn collect_candidates(results: Vec<Result<String>>) -> Result<Vec<String>> {
+    let mut messages = Vec::new();
+    let mut first_error = None;
+    
+    for result in results {
+        match result {
+            Ok(message) => messages.push(message),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    
+    match first_error {
+        Some(e) if messages.is_empty() => Err(e),
+        _ => Ok(unique(messages)),
+    }
+}
+
+// Drop messages that were generated more than once, keeping the original order
+pub fn unique(messages: Vec<String>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::with_capacity(messages.len());
+    for message in messages {
+        if !unique.contains(&message) {
+            unique.push(message);
+        }
+    }
+    unique
+}
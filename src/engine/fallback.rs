@@ -0,0 +1,222 @@
+use async_trait::async_trait;
+use log::{info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::error::{Error, Result};
+use crate::engine::engine::{AiEngine, Message, TokenCallback};
+
+// An engine along with the provider/model it talks to, for logging
+pub struct FallbackEntry {
+    pub label: String,
+    pub engine: Box<dyn AiEngine>,
+}
+
+// What is asked of each engine in turn
+#[derive(Clone, Copy)]
+enum Request<'a> {
+    Message,
+    Stream(TokenCallback<'a>),
+    Candidates(usize),
+}
+
+// Tries each engine in order, falling back to the next one when a provider fails
+pub struct FallbackEngine {
+    entries: Vec<FallbackEntry>,
+}
+
+impl FallbackEngine {
+    pub fn new(entries: Vec<FallbackEntry>) -> Self {
+        Self { entries }
+    }
+
+    async fn generate(&self, messages: Vec<Message>, diff: &str, request: Request<'_>) -> Result<Vec<String>> {
+        let mut last_error = None;
+
+        // Engines already retry failed requests, a failure here means moving on to the next one
+        for entry in &self.entries {
+            let streamed = AtomicBool::new(false);
+            let result = match request {
+                Request::Message => entry.engine.generate_commit_message(messages.clone(), diff).await.map(|m| vec![m]),
+                Request::Stream(on_token) => {
+                    let on_token = |token: &str| {
+                        streamed.store(true, Ordering::Relaxed);
+                        on_token(token);
+                    };
+                    entry.engine.generate_commit_message_stream(messages.clone(), diff, &on_token).await.map(|m| vec![m])
+                }
+                Request::Candidates(n) => entry.engine.generate_commit_messages(messages.clone(), diff, n).await,
+            };
+
+            match result {
+                Ok(generated) => {
+                    info!("Commit message generated by {}", entry.label);
+                    return Ok(generated);
+                }
+                // Another provider would stream its message after the partial one already shown
+                Err(e) if streamed.load(Ordering::Relaxed) => return Err(e),
+                Err(e) if !should_fall_back(&e) => return Err(e),
+                Err(e) => {
+                    warn!("{} failed: {}", entry.label, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| Error::AiProviderError("No AI provider configured".to_string())))
+    }
+}
+
+// Failures of the provider rather than of the request itself
+fn should_fall_back(error: &Error) -> bool {
+    is_transient(error) || matches!(error, Error::ContentBlocked(_))
+}
+
+// Timeouts, rate limits, server errors and unreachable providers, bad requests and keys fail everywhere
+fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::ApiError { status, .. } => matches!(status, 408 | 429 | 500..=599),
+        Error::Request(e) => e.is_connect() || e.is_timeout(),
+        Error::ProviderUnreachable(_) => true,
+        _ => false,
+    }
+}
+
+#[async_trait]
+impl AiEngine for FallbackEngine {
+    async fn generate_commit_message(&self, messages: Vec<Message>, diff: &str) -> Result<String> {
+        Ok(self.generate(messages, diff, Request::Message).await?.remove(0))
+    }
+
+    async fn generate_commit_message_stream(&self, messages: Vec<Message>, diff: &str, on_token: TokenCallback<'_>) -> Result<String> {
+        Ok(self.generate(messages, diff, Request::Stream(on_token)).await?.remove(0))
+    }
+
+    async fn generate_commit_messages(&self, messages: Vec<Message>, diff: &str, n: usize) -> Result<Vec<String>> {
+        self.generate(messages, diff, Request::Candidates(n)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::{Arc, Mutex};
+
+    fn api_error(status: u16) -> Error {
+        Error::ApiError { provider: "OpenAI".to_string(), status, message: "failed".to_string() }
+    }
+
+    // Fails with the given error, or answers when there is none
+    struct MockEngine {
+        error: Option<fn() -> Error>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl AiEngine for MockEngine {
+        async fn generate_commit_message(&self, _messages: Vec<Message>, _diff: &str) -> Result<String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match self.error {
+                Some(error) => Err(error()),
+                None => Ok("fix: mock".to_string()),
+            }
+        }
+    }
+
+    // Streams part of a message, then fails like a dropped connection
+    struct BrokenStreamEngine;
+
+    #[async_trait]
+    impl AiEngine for BrokenStreamEngine {
+        async fn generate_commit_message(&self, _messages: Vec<Message>, _diff: &str) -> Result<String> {
+            Err(api_error(503))
+        }
+
+        async fn generate_commit_message_stream(&self, _messages: Vec<Message>, _diff: &str, on_token: TokenCallback<'_>) -> Result<String> {
+            on_token("fix: par");
+            Err(api_error(503))
+        }
+    }
+
+    fn fallback(primary_error: fn() -> Error) -> (FallbackEngine, Arc<AtomicUsize>, Arc<AtomicUsize>) {
+        let primary_calls = Arc::new(AtomicUsize::new(0));
+        let secondary_calls = Arc::new(AtomicUsize::new(0));
+        let engine = FallbackEngine::new(vec![
+            FallbackEntry {
+                label: "primary".to_string(),
+                engine: Box::new(MockEngine { error: Some(primary_error), calls: primary_calls.clone() }),
+            },
+            FallbackEntry {
+                label: "secondary".to_string(),
+                engine: Box::new(MockEngine { error: None, calls: secondary_calls.clone() }),
+            },
+        ]);
+        (engine, primary_calls, secondary_calls)
+    }
+
+    #[test]
+    fn transient_errors_are_classified_by_status() {
+        for status in [408, 429, 500, 502, 503, 529] {
+            assert!(is_transient(&api_error(status)), "{} should be transient", status);
+        }
+        for status in [400, 401, 403, 404, 422] {
+            assert!(!is_transient(&api_error(status)), "{} should not be transient", status);
+        }
+
+        assert!(is_transient(&Error::ProviderUnreachable("down".to_string())));
+        assert!(!is_transient(&Error::AiProviderError("model not found".to_string())));
+        assert!(!is_transient(&Error::ContentBlocked("safety".to_string())));
+        assert!(!is_transient(&Error::Json(serde_json::from_str::<u8>("x").unwrap_err())));
+    }
+
+    #[tokio::test]
+    async fn connection_failures_are_transient() {
+        // Nothing listens on port 1
+        let error = reqwest::get("http://127.0.0.1:1").await.unwrap_err();
+        assert!(is_transient(&Error::Request(error)));
+    }
+
+    #[tokio::test]
+    async fn falls_back_when_the_provider_fails() {
+        let (engine, primary, secondary) = fallback(|| api_error(503));
+        assert_eq!(engine.generate_commit_message(Vec::new(), "diff").await.unwrap(), "fix: mock");
+        assert_eq!(primary.load(Ordering::SeqCst), 1);
+        assert_eq!(secondary.load(Ordering::SeqCst), 1);
+
+        let (engine, _, secondary) = fallback(|| Error::ContentBlocked("safety".to_string()));
+        assert!(engine.generate_commit_message(Vec::new(), "diff").await.is_ok());
+        assert_eq!(secondary.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn stops_on_request_errors() {
+        let (engine, primary, secondary) = fallback(|| api_error(401));
+        let error = engine.generate_commit_message(Vec::new(), "diff").await.unwrap_err();
+        assert!(matches!(error, Error::ApiError { status: 401, .. }));
+        assert_eq!(primary.load(Ordering::SeqCst), 1);
+        assert_eq!(secondary.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn streams_fall_back_only_before_the_first_token() {
+        let (engine, _, secondary) = fallback(|| api_error(503));
+        let shown = Mutex::new(String::new());
+        let on_token = |token: &str| shown.lock().unwrap().push_str(token);
+        assert_eq!(engine.generate_commit_message_stream(Vec::new(), "diff", &on_token).await.unwrap(), "fix: mock");
+        assert_eq!(*shown.lock().unwrap(), "fix: mock");
+        assert_eq!(secondary.load(Ordering::SeqCst), 1);
+
+        let secondary = Arc::new(AtomicUsize::new(0));
+        let engine = FallbackEngine::new(vec![
+            FallbackEntry { label: "primary".to_string(), engine: Box::new(BrokenStreamEngine) },
+            FallbackEntry {
+                label: "secondary".to_string(),
+                engine: Box::new(MockEngine { error: None, calls: secondary.clone() }),
+            },
+        ]);
+        let shown = Mutex::new(String::new());
+        let on_token = |token: &str| shown.lock().unwrap().push_str(token);
+        assert!(engine.generate_commit_message_stream(Vec::new(), "diff", &on_token).await.is_err());
+        assert_eq!(*shown.lock().unwrap(), "fix: par");
+        assert_eq!(secondary.load(Ordering::SeqCst), 0);
+    }
+}
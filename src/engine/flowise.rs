@@ -0,0 +1,174 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Serialize, Deserialize};
+use std::time::Duration;
+use crate::error::{Error, Result};
+use crate::engine::engine::{AiEngine, EngineConfig, Message};
+use crate::engine::http;
+
+#[derive(Debug, Clone)]
+pub struct FlowiseEngine {
+    config: EngineConfig,
+    client: Client,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FlowisePredictionRequest {
+    question: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    history: Vec<FlowiseHistoryMessage>,
+    override_config: FlowiseOverrideConfig,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FlowiseOverrideConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_message_prompt: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct FlowiseHistoryMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FlowisePredictionResponse {
+    #[serde(default)]
+    text: String,
+}
+
+impl FlowiseEngine {
+    pub fn new(config: EngineConfig) -> Self {
+        let client = http::build_client(&config, Duration::from_secs(120));
+
+        Self {
+            config,
+            client,
+        }
+    }
+
+    fn get_base_url(&self) -> String {
+        self.config.base_url.clone()
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| "http://localhost:3000".to_string())
+    }
+
+    fn get_prediction_url(&self) -> Result<String> {
+        let flow_id = self.config.flowise_flow_id.as_deref()
+            .ok_or_else(|| Error::InvalidConfiguration(
+                "OCO_FLOWISE_FLOW_ID must be set to the id of your Flowise chatflow".to_string()
+            ))?;
+
+        Ok(format!("{}/api/v1/prediction/{}", self.get_base_url(), flow_id))
+    }
+}
+
+#[async_trait]
+impl AiEngine for FlowiseEngine {
+    async fn generate_commit_message(&self, messages: Vec<Message>, diff: &str) -> Result<String> {
+        // The system prompt overrides the one configured in the chatflow
+        let system = messages.iter()
+            .filter(|m| m.role == "system")
+            .map(|m| m.content.clone())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // Flowise names the roles after who sent the message
+        let history = messages.iter()
+            .filter(|m| m.role != "system")
+            .map(|m| FlowiseHistoryMessage {
+                role: if m.role == "assistant" { "apiMessage" } else { "userMessage" }.to_string(),
+                content: m.content.clone(),
+            })
+            .collect();
+
+        // Prepare request
+        let request = FlowisePredictionRequest {
+            question: diff.to_string(),
+            history,
+            override_config: FlowiseOverrideConfig {
+                system_message_prompt: if system.is_empty() { None } else { Some(system) },
+            },
+        };
+
+        // Send request, the API key is only needed for protected chatflows
+        let mut request_builder = self.client.post(self.get_prediction_url()?)
+            .header("Content-Type", "application/json")
+            .json(&request);
+
+        if !self.config.api_key.is_empty() {
+            request_builder = request_builder.header("Authorization", format!("Bearer {}", self.config.api_key));
+        }
+
+        let response = http::send_with_retries(request_builder, self.config.max_retries).await?;
+
+        // Handle errors
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await?;
+            return Err(Error::ApiError { provider: "Flowise".to_string(), status: status.as_u16(), message: error_text });
+        }
+
+        // Parse response
+        let response: FlowisePredictionResponse = response.json().await?;
+
+        if response.text.is_empty() {
+            return Err(Error::EmptyCommitMessage);
+        }
+
+        Ok(response.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::engine::Sampling;
+    use crate::engine::http::mock::{request_body, serve_once};
+
+    #[tokio::test]
+    async fn sends_the_prediction_and_reads_the_text() {
+        let (url, server) = serve_once(
+            "200 OK",
+            r#"{"text":"fix: retry requests","chatId":"c1","chatMessageId":"m1"}"#,
+        ).await;
+
+        let engine = FlowiseEngine::new(EngineConfig {
+            api_key: "flowise-key".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            max_tokens_output: 500,
+            max_tokens_input: 4096,
+            base_url: Some(url),
+            azure_deployment: None,
+            azure_api_version: None,
+            flowise_flow_id: Some("flow-1".to_string()),
+            request_timeout: Some(Duration::from_secs(5)),
+            max_retries: 0,
+            sampling: Sampling::DETERMINISTIC,
+        });
+        let messages = vec![
+            Message::system("You write commit messages."),
+            Message::user("example diff"),
+            Message::assistant("fix: example"),
+        ];
+
+        let message = engine.generate_commit_message(messages, "the diff").await.unwrap();
+        assert_eq!(message, "fix: retry requests");
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /api/v1/prediction/flow-1 "));
+        assert!(request.to_lowercase().contains("authorization: bearer flowise-key"));
+
+        // The diff is the question, the earlier turns are history
+        let body = request_body(&request);
+        assert_eq!(body["question"], "the diff");
+        assert_eq!(body["history"], serde_json::json!([
+            { "role": "userMessage", "content": "example diff" },
+            { "role": "apiMessage", "content": "fix: example" },
+        ]));
+        assert_eq!(body["overrideConfig"]["systemMessagePrompt"], "You write commit messages.");
+    }
+}
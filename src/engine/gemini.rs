@@ -0,0 +1,274 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Serialize, Deserialize};
+use std::time::Duration;
+use crate::error::{Error, Result};
+use crate::engine::engine::{AiEngine, EngineConfig, Message};
+use crate::engine::http;
+use crate::utils::token_count::token_count;
+
+// Finish reasons Gemini uses when it refuses to return content
+const BLOCKED_FINISH_REASONS: &[&str] = &[
+    "SAFETY",
+    "RECITATION",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+];
+
+#[derive(Debug, Clone)]
+pub struct GeminiEngine {
+    config: EngineConfig,
+    client: Client,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiGenerateContentRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<GeminiContent>,
+    contents: Vec<GeminiContent>,
+    generation_config: GeminiGenerationConfig,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiGenerationConfig {
+    temperature: f32,
+    top_p: f32,
+    max_output_tokens: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiContent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<String>,
+    #[serde(default)]
+    parts: Vec<GeminiPart>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiPart {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiGenerateContentResponse {
+    #[serde(default)]
+    candidates: Vec<GeminiCandidate>,
+    prompt_feedback: Option<GeminiPromptFeedback>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiCandidate {
+    content: Option<GeminiContent>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiPromptFeedback {
+    block_reason: Option<String>,
+}
+
+impl GeminiContent {
+    fn text(role: Option<&str>, text: &str) -> Self {
+        Self {
+            role: role.map(|r| r.to_string()),
+            parts: vec![GeminiPart { text: text.to_string() }],
+        }
+    }
+}
+
+impl GeminiEngine {
+    pub fn new(config: EngineConfig) -> Self {
+        let client = http::build_client(&config, Duration::from_secs(120));
+
+        Self {
+            config,
+            client,
+        }
+    }
+
+    fn get_base_url(&self) -> String {
+        self.config.base_url.clone()
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| "https://generativelanguage.googleapis.com/v1beta".to_string())
+    }
+}
+
+#[async_trait]
+impl AiEngine for GeminiEngine {
+    async fn generate_commit_message(&self, messages: Vec<Message>, diff: &str) -> Result<String> {
+        // Gemini takes the system prompt as a separate instruction
+        let system = messages.iter()
+            .filter(|m| m.role == "system")
+            .map(|m| m.content.clone())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // Gemini only knows the "user" and "model" roles
+        let mut contents: Vec<GeminiContent> = messages.iter()
+            .filter(|m| m.role != "system")
+            .map(|m| {
+                let role = if m.role == "assistant" { "model" } else { "user" };
+                GeminiContent::text(Some(role), &m.content)
+            })
+            .collect();
+
+        // Add user message with diff
+        contents.push(GeminiContent::text(Some("user"), diff));
+
+        // Calculate token count
+        let request_tokens = token_count(&system) + contents.iter()
+            .flat_map(|c| c.parts.iter())
+            .map(|part| token_count(&part.text) + 4)
+            .sum::<usize>();
+
+        if request_tokens > self.config.max_tokens_input - self.config.max_tokens_output {
+            return Err(Error::TooManyTokens(request_tokens));
+        }
+
+        // Prepare request
+        let request = GeminiGenerateContentRequest {
+            system_instruction: if system.is_empty() { None } else { Some(GeminiContent::text(None, &system)) },
+            contents,
+            generation_config: GeminiGenerationConfig {
+                temperature: self.config.sampling.temperature,
+                top_p: self.config.sampling.top_p,
+                max_output_tokens: self.config.max_tokens_output,
+            },
+        };
+
+        // Send request
+        let request_builder = self.client.post(format!("{}/models/{}:generateContent", self.get_base_url(), self.config.model))
+            .header("Content-Type", "application/json")
+            .header("x-goog-api-key", &self.config.api_key)
+            .json(&request);
+        let response = http::send_with_retries(request_builder, self.config.max_retries).await?;
+
+        // Handle errors
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await?;
+            return Err(Error::ApiError { provider: "Gemini".to_string(), status: status.as_u16(), message: error_text });
+        }
+
+        // Parse response
+        let response: GeminiGenerateContentResponse = response.json().await?;
+
+        // The whole prompt can be rejected before any candidate is produced
+        if let Some(reason) = response.prompt_feedback.and_then(|f| f.block_reason) {
+            return Err(Error::ContentBlocked(format!("Gemini blocked the prompt ({})", reason)));
+        }
+
+        let candidate = response.candidates.into_iter()
+            .next()
+            .ok_or(Error::EmptyCommitMessage)?;
+
+        if let Some(reason) = candidate.finish_reason.as_deref() {
+            if BLOCKED_FINISH_REASONS.contains(&reason) {
+                return Err(Error::ContentBlocked(format!("Gemini stopped generating the response ({})", reason)));
+            }
+        }
+
+        // Get message content
+        let message = candidate.content
+            .map(|c| c.parts.into_iter().map(|p| p.text).collect::<String>())
+            .unwrap_or_default();
+
+        if message.is_empty() {
+            return Err(Error::EmptyCommitMessage);
+        }
+
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::engine::Sampling;
+    use crate::engine::http::mock::{request_body, serve_once};
+
+    fn engine(base_url: String) -> GeminiEngine {
+        GeminiEngine::new(EngineConfig {
+            api_key: "gemini-key".to_string(),
+            model: "gemini-1.5-flash".to_string(),
+            max_tokens_output: 500,
+            max_tokens_input: 4096,
+            base_url: Some(base_url),
+            azure_deployment: None,
+            azure_api_version: None,
+            flowise_flow_id: None,
+            request_timeout: Some(Duration::from_secs(5)),
+            max_retries: 0,
+            sampling: Sampling::DETERMINISTIC,
+        })
+    }
+
+    fn prompt() -> Vec<Message> {
+        vec![
+            Message::system("You write commit messages."),
+            Message::user("example diff"),
+            Message::assistant("fix: example"),
+        ]
+    }
+
+    #[tokio::test]
+    async fn maps_roles_to_gemini_contents() {
+        let (url, server) = serve_once(
+            "200 OK",
+            r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"feat: add "},{"text":"parser"}]},"finishReason":"STOP"}]}"#,
+        ).await;
+
+        let message = engine(url).generate_commit_message(prompt(), "the diff").await.unwrap();
+        assert_eq!(message, "feat: add parser");
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /models/gemini-1.5-flash:generateContent "));
+        assert!(request.to_lowercase().contains("x-goog-api-key: gemini-key"));
+
+        // The system prompt is an instruction, assistant turns belong to the model
+        let body = request_body(&request);
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "You write commit messages.");
+        assert!(body["systemInstruction"].get("role").is_none());
+        let contents: Vec<(&str, &str)> = body["contents"].as_array().unwrap().iter()
+            .map(|c| (c["role"].as_str().unwrap(), c["parts"][0]["text"].as_str().unwrap()))
+            .collect();
+        assert_eq!(contents, [("user", "example diff"), ("model", "fix: example"), ("user", "the diff")]);
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], 500);
+    }
+
+    #[tokio::test]
+    async fn blocked_prompts_are_reported() {
+        let (url, server) = serve_once("200 OK", r#"{"promptFeedback":{"blockReason":"SAFETY"}}"#).await;
+
+        let error = engine(url).generate_commit_message(prompt(), "the diff").await.unwrap_err();
+        server.await.unwrap();
+
+        match error {
+            Error::ContentBlocked(message) => assert_eq!(message, "Gemini blocked the prompt (SAFETY)"),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn blocked_responses_are_reported() {
+        let (url, server) = serve_once(
+            "200 OK",
+            r#"{"candidates":[{"content":{"role":"model","parts":[]},"finishReason":"SAFETY"}]}"#,
+        ).await;
+
+        let error = engine(url).generate_commit_message(prompt(), "the diff").await.unwrap_err();
+        server.await.unwrap();
+
+        match error {
+            Error::ContentBlocked(message) => assert_eq!(message, "Gemini stopped generating the response (SAFETY)"),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use log::warn;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use crate::error::Result;
+use crate::engine::engine::EngineConfig;
+
+// Delay before the first retry when the provider doesn't say how long to wait, doubled each time
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+// Never wait longer than this between attempts, whatever the provider asks for
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+// Remaining/reset header pairs providers send along with rate limited responses
+const RATE_LIMIT_HEADERS: &[(&str, &str)] = &[
+    // OpenAI, Groq, Mistral, DeepSeek
+    ("x-ratelimit-remaining-requests", "x-ratelimit-reset-requests"),
+    ("x-ratelimit-remaining-tokens", "x-ratelimit-reset-tokens"),
+    // Anthropic
+    ("anthropic-ratelimit-requests-remaining", "anthropic-ratelimit-requests-reset"),
+    ("anthropic-ratelimit-tokens-remaining", "anthropic-ratelimit-tokens-reset"),
+    ("anthropic-ratelimit-input-tokens-remaining", "anthropic-ratelimit-input-tokens-reset"),
+    ("anthropic-ratelimit-output-tokens-remaining", "anthropic-ratelimit-output-tokens-reset"),
+];
+
+// HTTP client with the configured timeout, or the engine's default
+pub fn build_client(config: &EngineConfig, default_timeout: Duration) -> Client {
+    Client::builder()
+        .timeout(config.request_timeout.unwrap_or(default_timeout))
+        .build()
+        .expect("Failed to create HTTP client")
+}
+
+// Send a request, retrying rate limits, server errors and connection failures
+pub async fn send_with_retries(request: RequestBuilder, max_retries: u32) -> Result<Response> {
+    let mut attempt = 0;
+
+    loop {
+        // Streaming bodies can't be sent twice, JSON bodies always can
+        let Some(current) = request.try_clone() else {
+            return Ok(request.send().await?);
+        };
+
+        let delay = match current.send().await {
+            Ok(response) if attempt < max_retries && is_retryable_status(response.status()) => {
+                let delay = retry_delay(response.headers(), response.status())
+                    .unwrap_or_else(|| backoff(attempt));
+                warn!("Request failed with {}, retrying in {:?}", response.status(), delay);
+                delay
+            }
+            Ok(response) => return Ok(response),
+            Err(e) if attempt < max_retries && (e.is_connect() || e.is_timeout()) => {
+                let delay = backoff(attempt);
+                warn!("Request failed: {}, retrying in {:?}", e, delay);
+                delay
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        tokio::time::sleep(delay.min(MAX_RETRY_DELAY)).await;
+        attempt += 1;
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::REQUEST_TIMEOUT || status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn backoff(attempt: u32) -> Duration {
+    INITIAL_BACKOFF * 2u32.saturating_pow(attempt)
+}
+
+// How long the provider asks us to wait, from Retry-After or its rate limit headers
+fn retry_delay(headers: &HeaderMap, status: StatusCode) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+
+    if let Some(delay) = header("retry-after-ms").and_then(|v| v.parse::<f64>().ok()).and_then(|ms| delay_from_secs(ms / 1000.0)) {
+        return Some(delay);
+    }
+
+    if let Some(value) = header("retry-after") {
+        return parse_seconds(value).or_else(|| parse_timestamp(value));
+    }
+
+    if status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+
+    // Wait for the exhausted limits to reset
+    RATE_LIMIT_HEADERS.iter()
+        .filter(|(remaining, _)| header(remaining) == Some("0"))
+        .filter_map(|(_, reset)| header(reset))
+        .filter_map(|value| parse_seconds(value).or_else(|| parse_duration(value)).or_else(|| parse_timestamp(value)))
+        .max()
+}
+
+// Servers can send anything, waits that aren't a finite positive number are ignored and long ones are capped
+fn delay_from_secs(seconds: f64) -> Option<Duration> {
+    if !seconds.is_finite() {
+        return None;
+    }
+
+    Duration::try_from_secs_f64(seconds.min(MAX_RETRY_DELAY.as_secs_f64())).ok()
+}
+
+fn parse_seconds(value: &str) -> Option<Duration> {
+    value.parse::<f64>().ok().and_then(delay_from_secs)
+}
+
+// Time until an HTTP date or RFC 3339 timestamp
+fn parse_timestamp(value: &str) -> Option<Duration> {
+    let at = DateTime::parse_from_rfc2822(value)
+        .or_else(|_| DateTime::parse_from_rfc3339(value))
+        .ok()?
+        .with_timezone(&Utc);
+
+    Some((at - Utc::now()).to_std().unwrap_or_default().min(MAX_RETRY_DELAY))
+}
+
+// Durations like `1m30s`, `6.5s` or `20ms` as sent in OpenAI's reset headers
+fn parse_duration(value: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = value;
+
+    while !rest.is_empty() {
+        let number_end = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let (number, after) = rest.split_at(number_end);
+        let number: f64 = number.parse().ok()?;
+
+        let unit_end = after.find(|c: char| c.is_ascii_digit()).unwrap_or(after.len());
+        let (unit, after) = after.split_at(unit_end);
+        let seconds = match unit {
+            "h" => number * 3600.0,
+            "m" => number * 60.0,
+            "s" => number,
+            "ms" => number / 1000.0,
+            _ => return None,
+        };
+
+        total += seconds;
+        rest = after;
+    }
+
+    delay_from_secs(total)
+}
+
+// A one-shot HTTP server standing in for a provider in engine tests
+#[cfg(test)]
+pub(crate) mod mock {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    // Answer a single request with the given status and body, handing back the raw request
+    pub async fn serve_once(status: &'static str, body: &'static str) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+
+            // Read the headers, then as much body as they announce
+            loop {
+                let read = socket.read(&mut buffer).await.unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..read]);
+
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end].lines()
+                        .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+            }
+
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+
+            String::from_utf8(request).unwrap()
+        });
+
+        (url, server)
+    }
+
+    // The JSON body of a raw request
+    pub fn request_body(request: &str) -> serde_json::Value {
+        serde_json::from_str(request.split("\r\n\r\n").nth(1).unwrap()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn parses_seconds() {
+        assert_eq!(parse_seconds("2.5"), Some(Duration::from_millis(2500)));
+        assert_eq!(parse_seconds("0"), Some(Duration::ZERO));
+        assert_eq!(parse_seconds("1e300"), Some(MAX_RETRY_DELAY));
+        assert_eq!(parse_seconds("inf"), None);
+        assert_eq!(parse_seconds("-inf"), None);
+        assert_eq!(parse_seconds("NaN"), None);
+        assert_eq!(parse_seconds("-1"), None);
+        assert_eq!(parse_seconds("soon"), None);
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("1m30s"), Some(MAX_RETRY_DELAY));
+        assert_eq!(parse_duration("6.5s"), Some(Duration::from_millis(6500)));
+        assert_eq!(parse_duration("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(parse_duration("99999999999999999999h"), Some(MAX_RETRY_DELAY));
+        assert_eq!(parse_duration("inf"), None);
+        assert_eq!(parse_duration("5x"), None);
+        assert_eq!(parse_duration("s"), None);
+    }
+
+    #[test]
+    fn retry_delay_prefers_retry_after() {
+        let delay = retry_delay(&headers(&[("retry-after-ms", "1500"), ("retry-after", "30")]), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(delay, Some(Duration::from_millis(1500)));
+
+        let delay = retry_delay(&headers(&[("retry-after", "3")]), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(delay, Some(Duration::from_secs(3)));
+
+        let delay = retry_delay(&headers(&[("retry-after-ms", "1e30")]), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(delay, Some(MAX_RETRY_DELAY));
+    }
+
+    #[test]
+    fn retry_delay_ignores_malformed_values() {
+        assert_eq!(retry_delay(&headers(&[("retry-after", "inf")]), StatusCode::SERVICE_UNAVAILABLE), None);
+        assert_eq!(retry_delay(&headers(&[("retry-after", "later")]), StatusCode::TOO_MANY_REQUESTS), None);
+        assert_eq!(retry_delay(&headers(&[]), StatusCode::TOO_MANY_REQUESTS), None);
+    }
+
+    #[test]
+    fn retry_delay_waits_for_exhausted_rate_limits() {
+        let limits = headers(&[
+            ("x-ratelimit-remaining-requests", "0"),
+            ("x-ratelimit-reset-requests", "2s"),
+            ("x-ratelimit-remaining-tokens", "100"),
+            ("x-ratelimit-reset-tokens", "50s"),
+        ]);
+        assert_eq!(retry_delay(&limits, StatusCode::TOO_MANY_REQUESTS), Some(Duration::from_secs(2)));
+
+        // Only rate limited responses are told when limits reset
+        assert_eq!(retry_delay(&limits, StatusCode::INTERNAL_SERVER_ERROR), None);
+
+        let huge = headers(&[("x-ratelimit-remaining-tokens", "0"), ("x-ratelimit-reset-tokens", "1e400")]);
+        assert_eq!(retry_delay(&huge, StatusCode::TOO_MANY_REQUESTS), None);
+    }
+}
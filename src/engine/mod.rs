@@ -5,39 +5,104 @@ pub mod azure;
 pub mod ollama;
 pub mod gemini;
 pub mod flowise;
-pub mod groq;
-pub mod mistral;
-pub mod mlx;
-pub mod deepseek;
+pub mod chunked;
+pub mod fallback;
+pub mod http;
 pub mod test;
 
-use crate::error::{Error, Result};
+use std::str::FromStr;
+use std::time::Duration;
+use log::warn;
+use crate::error::Result;
 use crate::commands::config::{Config, AiProvider};
-use crate::engine::engine::{AiEngine, EngineConfig, Message};
+use crate::engine::engine::{AiEngine, EngineConfig, Message, Sampling};
 
-// Get the appropriate AI engine based on configuration
+// Get the appropriate AI engine based on configuration, requests are sent as they are
 pub fn get_engine(config: &Config) -> Result<Box<dyn AiEngine>> {
+    build_engine(config, Sampling::DETERMINISTIC, false)
+}
+
+// Get an engine for commit messages, diffs too large for the model are split and the partial messages merged
+pub fn get_commit_engine(config: &Config) -> Result<Box<dyn AiEngine>> {
+    get_commit_engine_with_sampling(config, Sampling::DETERMINISTIC)
+}
+
+// Get a commit message engine sampling with the given settings, e.g. to generate alternative messages
+pub fn get_commit_engine_with_sampling(config: &Config, sampling: Sampling) -> Result<Box<dyn AiEngine>> {
+    build_engine(config, sampling, true)
+}
+
+// Chunking only makes sense for commit messages, other requests would come back as merged commit messages
+fn build_engine(config: &Config, sampling: Sampling, chunked: bool) -> Result<Box<dyn AiEngine>> {
+    let primary = get_configured_engine(config, sampling, chunked)?;
+    
+    let fallbacks = config.fallback_configs()?;
+    if fallbacks.is_empty() {
+        return Ok(primary);
+    }
+    
+    let mut entries = vec![fallback::FallbackEntry {
+        label: engine_label(config),
+        engine: primary,
+    }];
+    
+    for fallback_config in fallbacks {
+        // A fallback that can't be set up shouldn't keep the others from working
+        match get_configured_engine(&fallback_config, sampling, chunked) {
+            Ok(engine) => entries.push(fallback::FallbackEntry {
+                label: engine_label(&fallback_config),
+                engine,
+            }),
+            Err(e) => warn!("Skipping fallback {}: {}", engine_label(&fallback_config), e),
+        }
+    }
+    
+    Ok(Box::new(fallback::FallbackEngine::new(entries)))
+}
+
+fn engine_label(config: &Config) -> String {
+    format!("{} ({})", config.ai_provider, config.model)
+}
+
+fn get_configured_engine(config: &Config, sampling: Sampling, chunked: bool) -> Result<Box<dyn AiEngine>> {
+    let engine = get_provider_engine(config, sampling)?;
+    if !chunked {
+        return Ok(engine);
+    }
+    
+    // Split diffs that do not fit into the model's context, whatever the provider
+    Ok(Box::new(chunked::ChunkedEngine::new(
+        engine,
+        config.tokens_max_input,
+        config.tokens_max_output,
+    )))
+}
+
+// Get the engine talking to the configured provider
+fn get_provider_engine(config: &Config, sampling: Sampling) -> Result<Box<dyn AiEngine>> {
     let provider = AiProvider::from_str(&config.ai_provider)?;
     
     let engine_config = EngineConfig {
         model: config.model.clone(),
         max_tokens_output: config.tokens_max_output,
         max_tokens_input: config.tokens_max_input,
-        api_key: config.api_key.clone().unwrap_or_default(),
+        api_key: config.resolved_api_key()?.unwrap_or_default(),
         base_url: config.api_url.clone(),
+        azure_deployment: config.azure_deployment.clone(),
+        azure_api_version: config.azure_api_version.clone(),
+        flowise_flow_id: config.flowise_flow_id.clone(),
+        request_timeout: config.request_timeout.map(Duration::from_secs),
+        max_retries: config.max_retries,
+        sampling,
     };
     
     match provider {
-        AiProvider::OpenAi => Ok(Box::new(openai::OpenAiEngine::new(engine_config))),
+        AiProvider::Compatible(compatible) => Ok(Box::new(openai::OpenAiEngine::for_provider(engine_config, compatible))),
         AiProvider::Anthropic => Ok(Box::new(anthropic::AnthropicEngine::new(engine_config))),
         AiProvider::Azure => Ok(Box::new(azure::AzureEngine::new(engine_config))),
         AiProvider::Ollama => Ok(Box::new(ollama::OllamaEngine::new(engine_config))),
         AiProvider::Gemini => Ok(Box::new(gemini::GeminiEngine::new(engine_config))),
         AiProvider::Flowise => Ok(Box::new(flowise::FlowiseEngine::new(engine_config))),
-        AiProvider::Groq => Ok(Box::new(groq::GroqEngine::new(engine_config))),
-        AiProvider::Mistral => Ok(Box::new(mistral::MistralEngine::new(engine_config))),
-        AiProvider::Mlx => Ok(Box::new(mlx::MlxEngine::new(engine_config))),
-        AiProvider::Deepseek => Ok(Box::new(deepseek::DeepseekEngine::new(engine_config))),
         AiProvider::Test => Ok(Box::new(test::TestEngine::new())),
     }
 }
\ No newline at end of file
@@ -0,0 +1,315 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Serialize, Deserialize};
+use std::time::Duration;
+use crate::error::{Error, Result};
+use crate::engine::engine::{AiEngine, EngineConfig, Message, TokenCallback};
+use crate::engine::http;
+use crate::utils::token_count::token_count;
+
+#[derive(Debug, Clone)]
+pub struct OllamaEngine {
+    config: EngineConfig,
+    client: Client,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaChatRequest {
+    model: String,
+    messages: Vec<OllamaMessage>,
+    stream: bool,
+    options: OllamaOptions,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaOptions {
+    temperature: f32,
+    top_p: f32,
+    num_predict: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OllamaMessage {
+    role: String,
+    content: String,
+}
+
+// A full response, or a single line of a streamed response
+#[derive(Debug, Deserialize)]
+struct OllamaChatResponse {
+    #[serde(default)]
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+impl OllamaEngine {
+    pub fn new(config: EngineConfig) -> Self {
+        // Local models can be slow to load on first use
+        let client = http::build_client(&config, Duration::from_secs(300));
+
+        Self {
+            config,
+            client,
+        }
+    }
+
+    fn get_base_url(&self) -> String {
+        self.config.base_url.clone()
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| "http://localhost:11434".to_string())
+    }
+
+    // Turn an Ollama error string into a readable provider error
+    fn map_error(&self, error: &str) -> Error {
+        if is_missing_model(error) {
+            Error::AiProviderError(format!(
+                "Ollama model '{}' is not pulled. Run `ollama pull {}` and try again.",
+                self.config.model, self.config.model
+            ))
+        } else {
+            Error::AiProviderError(format!("Ollama error: {}", error))
+        }
+    }
+
+    // Parse either a single JSON object or newline-delimited JSON chunks
+    fn parse_chat_response(&self, body: &str) -> Result<String> {
+        if let Ok(response) = serde_json::from_str::<OllamaChatResponse>(body) {
+            if let Some(error) = response.error {
+                return Err(self.map_error(&error));
+            }
+            return Ok(response.message.map(|m| m.content).unwrap_or_default());
+        }
+
+        let mut content = String::new();
+        for line in body.lines().filter(|l| !l.trim().is_empty()) {
+            let chunk: OllamaChatResponse = serde_json::from_str(line)?;
+            if let Some(error) = chunk.error {
+                return Err(self.map_error(&error));
+            }
+            if let Some(message) = chunk.message {
+                content.push_str(&message.content);
+            }
+        }
+
+        Ok(content)
+    }
+
+    fn build_request(&self, messages: &[Message], diff: &str, stream: bool) -> Result<OllamaChatRequest> {
+        let mut ollama_messages: Vec<OllamaMessage> = messages.iter()
+            .map(|m| OllamaMessage {
+                role: m.role.clone(),
+                content: m.content.clone(),
+            })
+            .collect();
+
+        // Add user message with diff
+        ollama_messages.push(OllamaMessage {
+            role: "user".to_string(),
+            content: diff.to_string(),
+        });
+
+        // Calculate token count
+        let request_tokens = ollama_messages.iter()
+            .map(|msg| token_count(&msg.content) + 4)
+            .sum::<usize>();
+
+        if request_tokens > self.config.max_tokens_input - self.config.max_tokens_output {
+            return Err(Error::TooManyTokens(request_tokens));
+        }
+
+        Ok(OllamaChatRequest {
+            model: self.config.model.clone(),
+            messages: ollama_messages,
+            stream,
+            options: OllamaOptions {
+                temperature: self.config.sampling.temperature,
+                top_p: self.config.sampling.top_p,
+                num_predict: self.config.max_tokens_output,
+            },
+        })
+    }
+
+    // Send a chat request and turn connection and HTTP failures into provider errors
+    async fn send(&self, request: &OllamaChatRequest) -> Result<reqwest::Response> {
+        // The API key is only needed behind an authenticating proxy
+        let mut request_builder = self.client.post(format!("{}/api/chat", self.get_base_url()))
+            .header("Content-Type", "application/json")
+            .json(request);
+
+        if !self.config.api_key.is_empty() {
+            request_builder = request_builder.header("Authorization", format!("Bearer {}", self.config.api_key));
+        }
+
+        let response = http::send_with_retries(request_builder, self.config.max_retries).await.map_err(|e| match e {
+            Error::Request(e) if e.is_connect() => Error::ProviderUnreachable(format!(
+                "Could not connect to Ollama at {}. Is `ollama serve` running?",
+                self.get_base_url()
+            )),
+            e => e,
+        })?;
+
+        // Handle errors
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await?;
+            let error = serde_json::from_str::<OllamaChatResponse>(&body)
+                .ok()
+                .and_then(|r| r.error)
+                .unwrap_or(body);
+
+            // Keep the status so the fallback engine can tell an overloaded server from a bad request
+            if is_missing_model(&error) {
+                return Err(self.map_error(&error));
+            }
+            return Err(Error::ApiError { provider: "Ollama".to_string(), status: status.as_u16(), message: error });
+        }
+
+        Ok(response)
+    }
+}
+
+fn is_missing_model(error: &str) -> bool {
+    error.contains("model") && error.contains("not found")
+}
+
+#[async_trait]
+impl AiEngine for OllamaEngine {
+    async fn generate_commit_message(&self, messages: Vec<Message>, diff: &str) -> Result<String> {
+        let request = self.build_request(&messages, diff, false)?;
+        let response = self.send(&request).await?;
+
+        let body = response.text().await?;
+        let message = self.parse_chat_response(&body)?;
+
+        if message.is_empty() {
+            return Err(Error::EmptyCommitMessage);
+        }
+
+        Ok(message)
+    }
+
+    async fn generate_commit_message_stream(&self, messages: Vec<Message>, diff: &str, on_token: TokenCallback<'_>) -> Result<String> {
+        let request = self.build_request(&messages, diff, true)?;
+        let mut response = self.send(&request).await?;
+
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut message = String::new();
+
+        while let Some(bytes) = response.chunk().await? {
+            buffer.extend_from_slice(&bytes);
+
+            // Each complete line is one JSON chunk
+            while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=newline).collect();
+                let line = String::from_utf8_lossy(&line);
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let chunk: OllamaChatResponse = serde_json::from_str(line.trim())?;
+                if let Some(error) = chunk.error {
+                    return Err(self.map_error(&error));
+                }
+                if let Some(content) = chunk.message.map(|m| m.content) {
+                    on_token(&content);
+                    message.push_str(&content);
+                }
+            }
+        }
+
+        if message.is_empty() {
+            return Err(Error::EmptyCommitMessage);
+        }
+
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::engine::engine::Sampling;
+    use crate::engine::http::mock::{request_body, serve_once};
+
+    fn engine(base_url: String) -> OllamaEngine {
+        OllamaEngine::new(EngineConfig {
+            api_key: String::new(),
+            model: "mistral".to_string(),
+            max_tokens_output: 500,
+            max_tokens_input: 4096,
+            base_url: Some(base_url),
+            azure_deployment: None,
+            azure_api_version: None,
+            flowise_flow_id: None,
+            request_timeout: Some(Duration::from_secs(5)),
+            max_retries: 0,
+            sampling: Sampling::DETERMINISTIC,
+        })
+    }
+
+    fn prompt() -> Vec<Message> {
+        vec![Message::system("You write commit messages.")]
+    }
+
+    #[tokio::test]
+    async fn streams_json_lines() {
+        let (url, server) = serve_once(
+            "200 OK",
+            concat!(
+                r#"{"message":{"role":"assistant","content":"feat: add "},"done":false}"#, "\n",
+                r#"{"message":{"role":"assistant","content":"parser"},"done":false}"#, "\n",
+                r#"{"done":true}"#, "\n",
+            ),
+        ).await;
+
+        let tokens = Mutex::new(Vec::new());
+        let on_token = |token: &str| tokens.lock().unwrap().push(token.to_string());
+        let message = engine(url).generate_commit_message_stream(prompt(), "the diff", &on_token).await.unwrap();
+        assert_eq!(message, "feat: add parser");
+        assert_eq!(*tokens.lock().unwrap(), ["feat: add ", "parser"]);
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /api/chat "));
+        assert!(!request.to_lowercase().contains("authorization:"));
+
+        let body = request_body(&request);
+        assert_eq!(body["model"], "mistral");
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][1]["content"], "the diff");
+        assert_eq!(body["options"]["num_predict"], 500);
+    }
+
+    #[tokio::test]
+    async fn explains_missing_models() {
+        let (url, server) = serve_once("404 Not Found", r#"{"error":"model \"mistral\" not found, try pulling it first"}"#).await;
+
+        let error = engine(url).generate_commit_message(prompt(), "the diff").await.unwrap_err();
+        server.await.unwrap();
+
+        match error {
+            Error::AiProviderError(message) => assert_eq!(
+                message,
+                "Ollama model 'mistral' is not pulled. Run `ollama pull mistral` and try again."
+            ),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_an_unreachable_server() {
+        // Nothing listens on a port that was just released
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let error = engine(url.clone()).generate_commit_message(prompt(), "the diff").await.unwrap_err();
+
+        match error {
+            Error::ProviderUnreachable(message) => assert!(message.contains(&url), "unexpected message: {}", message),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+}
@@ -1,127 +1,317 @@
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Serialize, Deserialize};
-use serde_json::json;
 use std::time::Duration;
 use crate::error::{Error, Result};
-use crate::engine::engine::{AiEngine, EngineConfig, Message};
+use crate::engine::engine::{generate_in_parallel, unique, AiEngine, EngineConfig, Message, TokenCallback};
+use crate::engine::http;
 use crate::utils::token_count::token_count;
 
+// How a provider expects the API key to be sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthStyle {
+    Bearer,
+    None,
+}
+
+// A vendor speaking the OpenAI chat-completions dialect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompatibleProvider {
+    pub name: &'static str,
+    pub display_name: &'static str,
+    pub default_base_url: &'static str,
+    pub auth: AuthStyle,
+    pub default_model: &'static str,
+    // Whether several choices can be requested at once with `n`
+    pub multiple_choices: bool,
+}
+
+// Add new OpenAI-compatible vendors here
+pub static COMPATIBLE_PROVIDERS: &[CompatibleProvider] = &[
+    CompatibleProvider {
+        name: "openai",
+        display_name: "OpenAI",
+        default_base_url: "https://api.openai.com/v1",
+        auth: AuthStyle::Bearer,
+        default_model: "gpt-4o-mini",
+        multiple_choices: true,
+    },
+    CompatibleProvider {
+        name: "groq",
+        display_name: "Groq",
+        default_base_url: "https://api.groq.com/openai/v1",
+        auth: AuthStyle::Bearer,
+        default_model: "llama3-70b-8192",
+        multiple_choices: false,
+    },
+    CompatibleProvider {
+        name: "mistral",
+        display_name: "Mistral",
+        default_base_url: "https://api.mistral.ai/v1",
+        auth: AuthStyle::Bearer,
+        default_model: "mistral-small-latest",
+        multiple_choices: false,
+    },
+    CompatibleProvider {
+        name: "deepseek",
+        display_name: "DeepSeek",
+        default_base_url: "https://api.deepseek.com/v1",
+        auth: AuthStyle::Bearer,
+        default_model: "deepseek-chat",
+        multiple_choices: false,
+    },
+    CompatibleProvider {
+        name: "mlx",
+        display_name: "MLX",
+        default_base_url: "http://localhost:8080/v1",
+        auth: AuthStyle::None,
+        default_model: "mlx-community/Llama-3.2-3B-Instruct-4bit",
+        multiple_choices: false,
+    },
+];
+
+// Look up an OpenAI-compatible provider by its config name
+pub fn find_compatible_provider(name: &str) -> Option<&'static CompatibleProvider> {
+    COMPATIBLE_PROVIDERS.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
 #[derive(Debug, Clone)]
 pub struct OpenAiEngine {
     config: EngineConfig,
     client: Client,
+    provider: &'static CompatibleProvider,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct OpenAiChatCompletionRequest {
-    model: String,
-    messages: Vec<OpenAiMessage>,
-    temperature: f32,
-    top_p: f32,
-    max_tokens: usize,
+pub(crate) struct OpenAiChatCompletionRequest {
+    pub(crate) model: String,
+    pub(crate) messages: Vec<OpenAiMessage>,
+    pub(crate) temperature: f32,
+    pub(crate) top_p: f32,
+    pub(crate) max_tokens: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) n: Option<usize>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) stream: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct OpenAiMessage {
-    role: String,
-    content: String,
+pub(crate) struct OpenAiMessage {
+    pub(crate) role: String,
+    pub(crate) content: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct OpenAiChatCompletionResponse {
+    pub(crate) choices: Vec<OpenAiChoice>,
 }
 
 #[derive(Debug, Deserialize)]
-struct OpenAiChatCompletionResponse {
-    choices: Vec<OpenAiChoice>,
+pub(crate) struct OpenAiChoice {
+    pub(crate) message: OpenAiMessage,
 }
 
+// A single server-sent event of a streamed completion
 #[derive(Debug, Deserialize)]
-struct OpenAiChoice {
-    message: OpenAiMessage,
+pub(crate) struct OpenAiChatCompletionChunk {
+    #[serde(default)]
+    pub(crate) choices: Vec<OpenAiChunkChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct OpenAiChunkChoice {
+    pub(crate) delta: OpenAiDelta,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct OpenAiDelta {
+    #[serde(default)]
+    pub(crate) content: Option<String>,
+}
+
+// Build a chat completion request, shared by engines speaking the OpenAI dialect
+pub(crate) fn build_chat_completion_request(config: &EngineConfig, messages: &[Message], diff: &str) -> Result<OpenAiChatCompletionRequest> {
+    let mut openai_messages: Vec<OpenAiMessage> = Vec::with_capacity(messages.len() + 1);
+    
+    // Keep the conversation in order, earlier user turns carry the examples and the diff being repaired
+    for msg in messages {
+        openai_messages.push(OpenAiMessage {
+            role: msg.role.clone(),
+            content: msg.content.clone(),
+        });
+    }
+    
+    // Add user message with diff
+    openai_messages.push(OpenAiMessage {
+        role: "user".to_string(),
+        content: diff.to_string(),
+    });
+    
+    // Calculate token count
+    let request_tokens = openai_messages.iter()
+        .map(|msg| token_count(&msg.content) + 4)
+        .sum::<usize>();
+        
+    if request_tokens > config.max_tokens_input - config.max_tokens_output {
+        return Err(Error::TooManyTokens(request_tokens));
+    }
+    
+    Ok(OpenAiChatCompletionRequest {
+        model: config.model.clone(),
+        messages: openai_messages,
+        temperature: config.sampling.temperature,
+        top_p: config.sampling.top_p,
+        max_tokens: config.max_tokens_output,
+        n: None,
+        stream: false,
+    })
+}
+
+// Turn a chat completion HTTP response into the commit message
+pub(crate) async fn parse_chat_completion_response(response: reqwest::Response, provider_name: &str) -> Result<String> {
+    let mut choices = parse_chat_completion_choices(response, provider_name).await?;
+    Ok(choices.remove(0))
+}
+
+// Turn a chat completion HTTP response into one commit message per choice
+pub(crate) async fn parse_chat_completion_choices(response: reqwest::Response, provider_name: &str) -> Result<Vec<String>> {
+    // Handle errors
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await?;
+        return Err(Error::ApiError { provider: provider_name.to_string(), status: status.as_u16(), message: error_text });
+    }
+    
+    // Parse response
+    let response: OpenAiChatCompletionResponse = response.json().await?;
+    
+    // Get message contents
+    let messages: Vec<String> = response.choices.into_iter()
+        .map(|choice| choice.message.content)
+        .filter(|content| !content.is_empty())
+        .collect();
+    
+    if messages.is_empty() {
+        return Err(Error::EmptyCommitMessage);
+    }
+    
+    Ok(messages)
+}
+
+// Read a streamed chat completion, reporting each delta as it arrives
+pub(crate) async fn read_chat_completion_stream(mut response: reqwest::Response, provider_name: &str, on_token: TokenCallback<'_>) -> Result<String> {
+    // Handle errors
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await?;
+        return Err(Error::ApiError { provider: provider_name.to_string(), status: status.as_u16(), message: error_text });
+    }
+    
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut message = String::new();
+    
+    'events: while let Some(bytes) = response.chunk().await? {
+        buffer.extend_from_slice(&bytes);
+        
+        // Only handle complete lines, a chunk can end in the middle of one
+        while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim().strip_prefix("data:") else {
+                continue;
+            };
+            
+            let data = data.trim();
+            if data == "[DONE]" {
+                break 'events;
+            }
+            
+            let chunk: OpenAiChatCompletionChunk = serde_json::from_str(data)?;
+            for content in chunk.choices.into_iter().filter_map(|c| c.delta.content) {
+                on_token(&content);
+                message.push_str(&content);
+            }
+        }
+    }
+    
+    if message.is_empty() {
+        return Err(Error::EmptyCommitMessage);
+    }
+    
+    Ok(message)
 }
 
 impl OpenAiEngine {
     pub fn new(config: EngineConfig) -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(120))
-            .build()
-            .expect("Failed to create HTTP client");
+        Self::for_provider(config, &COMPATIBLE_PROVIDERS[0])
+    }
+    
+    pub fn for_provider(mut config: EngineConfig, provider: &'static CompatibleProvider) -> Self {
+        if config.model.is_empty() {
+            config.model = provider.default_model.to_string();
+        }
+        
+        let client = http::build_client(&config, Duration::from_secs(120));
             
         Self {
             config,
             client,
+            provider,
         }
     }
     
     fn get_base_url(&self) -> String {
-        self.config.base_url.clone().unwrap_or_else(|| "https://api.openai.com/v1".to_string())
+        self.config.base_url.clone()
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| self.provider.default_base_url.to_string())
+    }
+    
+    // Send a chat completion request with the provider's authentication
+    async fn send(&self, request: &OpenAiChatCompletionRequest) -> Result<reqwest::Response> {
+        let mut request_builder = self.client.post(format!("{}/chat/completions", self.get_base_url()))
+            .header("Content-Type", "application/json")
+            .json(request);
+        
+        request_builder = match self.provider.auth {
+            AuthStyle::Bearer => request_builder.header("Authorization", format!("Bearer {}", self.config.api_key)),
+            AuthStyle::None => request_builder,
+        };
+        
+        http::send_with_retries(request_builder, self.config.max_retries).await
     }
 }
 
 #[async_trait]
 impl AiEngine for OpenAiEngine {
     async fn generate_commit_message(&self, messages: Vec<Message>, diff: &str) -> Result<String> {
-        // Add diff to the last message
-        let mut openai_messages: Vec<OpenAiMessage> = Vec::with_capacity(messages.len() + 1);
-        
-        // Add system and other messages
-        for msg in messages.iter().filter(|m| m.role != "user") {
-            openai_messages.push(OpenAiMessage {
-                role: msg.role.clone(),
-                content: msg.content.clone(),
-            });
-        }
-        
-        // Add user message with diff
-        openai_messages.push(OpenAiMessage {
-            role: "user".to_string(),
-            content: diff.to_string(),
-        });
-        
-        // Calculate token count
-        let request_tokens = openai_messages.iter()
-            .map(|msg| token_count(&msg.content) + 4)
-            .sum::<usize>();
-            
-        if request_tokens > self.config.max_tokens_input - self.config.max_tokens_output {
-            return Err(Error::TooManyTokens(request_tokens));
-        }
-        
         // Prepare request
-        let request = OpenAiChatCompletionRequest {
-            model: self.config.model.clone(),
-            messages: openai_messages,
-            temperature: 0.0,
-            top_p: 0.1,
-            max_tokens: self.config.max_tokens_output,
-        };
+        let request = build_chat_completion_request(&self.config, &messages, diff)?;
         
         // Send request
-        let response = self.client.post(format!("{}/chat/completions", self.get_base_url()))
-            .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", self.config.api_key))
-            .json(&request)
-            .send()
-            .await?;
+        let response = self.send(&request).await?;
             
-        // Handle errors
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(Error::AiProviderError(format!("OpenAI error: {}", error_text)));
-        }
+        parse_chat_completion_response(response, self.provider.display_name).await
+    }
+    
+    async fn generate_commit_message_stream(&self, messages: Vec<Message>, diff: &str, on_token: TokenCallback<'_>) -> Result<String> {
+        let mut request = build_chat_completion_request(&self.config, &messages, diff)?;
+        request.stream = true;
         
-        // Parse response
-        let response: OpenAiChatCompletionResponse = response.json().await?;
+        let response = self.send(&request).await?;
         
-        // Get message content
-        if response.choices.is_empty() {
-            return Err(Error::EmptyCommitMessage);
+        read_chat_completion_stream(response, self.provider.display_name, on_token).await
+    }
+    
+    async fn generate_commit_messages(&self, messages: Vec<Message>, diff: &str, n: usize) -> Result<Vec<String>> {
+        if !self.provider.multiple_choices || n <= 1 {
+            return generate_in_parallel(self, messages, diff, n).await;
         }
         
-        let message = response.choices[0].message.content.clone();
+        let mut request = build_chat_completion_request(&self.config, &messages, diff)?;
+        request.n = Some(n);
         
-        if message.is_empty() {
-            return Err(Error::EmptyCommitMessage);
-        }
+        let response = self.send(&request).await?;
         
-        Ok(message)
+        Ok(unique(parse_chat_completion_choices(response, self.provider.display_name).await?))
     }
-}
\ No newline at end of file
+}
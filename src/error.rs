@@ -17,6 +17,9 @@ pub enum Error {
     #[error("TOML error: {0}")]
     Toml(#[from] toml::de::Error),
     
+    #[error("TOML error: {0}")]
+    TomlSerialize(#[from] toml::ser::Error),
+    
     #[error("Config error: {0}")]
     Config(#[from] config::ConfigError),
     
@@ -44,15 +47,33 @@ pub enum Error {
     #[error("Unsupported AI provider: {0}")]
     UnsupportedAiProvider(String),
     
+    // Failures that have no HTTP status, like a model that isn't installed
     #[error("AI provider error: {0}")]
     AiProviderError(String),
     
+    // HTTP errors from every provider, the fallback chain needs the status to tell
+    // an overloaded provider from a bad request
+    #[error("{provider} error ({status}): {message}")]
+    ApiError { provider: String, status: u16, message: String },
+    
+    #[error("{0}")]
+    ProviderUnreachable(String),
+    
+    #[error("Content blocked by AI provider: {0}")]
+    ContentBlocked(String),
+    
     #[error("Commitlint error: {0}")]
     CommitlintError(String),
     
     #[error("Hook error: {0}")]
     HookError(String),
     
+    #[error("Secret storage error: {0}")]
+    SecretError(String),
+    
+    #[error("Cannot rewrite history: {0}")]
+    RewriteError(String),
+    
     #[error("{0}")]
     Generic(String),
 }
@@ -2,7 +2,7 @@ use anyhow::Result;
 use clap::Parser;
 use log::error;
 use opencommit::cli::Cli;
-use opencommit::commands::{commit, config, githook, commitlint};
+use opencommit::commands::{commit, config, githook, commitlint, split, reword, squash, pr};
 use opencommit::migrations::run_migrations;
 use opencommit::utils::version::check_latest_version;
 
@@ -10,9 +10,31 @@ use opencommit::utils::version::check_latest_version;
 async fn main() -> Result<()> {
     env_logger::init();
     
+    // When run through the prepare-commit-msg symlink, git's arguments are not a CLI
+    if githook::is_hook_called() {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let Some(commit_msg_file) = args.first() else {
+            error!("prepare-commit-msg hook called without a commit message file");
+            return Ok(());
+        };
+        
+        // Never block the commit, git falls back to the editor with the original message
+        if let Err(e) = githook::prepare_commit_msg_hook(commit_msg_file, args.get(1).map(String::as_str)).await {
+            error!("Failed to generate commit message in hook: {}", e);
+            eprintln!("OpenCommit hook failed: {}", e);
+        }
+        
+        return Ok(());
+    }
+    
     // Parse command line arguments
     let cli = Cli::parse();
     
+    // The config is loaded in many places, the environment reaches all of them
+    if let Some(profile) = &cli.profile {
+        std::env::set_var("OCO_PROFILE", profile);
+    }
+    
     // Run migrations for config if needed
     if let Err(e) = run_migrations().await {
         error!("Failed to run migrations: {}", e);
@@ -37,6 +59,18 @@ async fn main() -> Result<()> {
             opencommit::cli::Commands::Commitlint { action } => {
                 commitlint::handle_commitlint_command(action).await
             }
+            opencommit::cli::Commands::Split { yes, fgm, context } => {
+                split::execute_split(context, fgm, yes).await
+            }
+            opencommit::cli::Commands::Reword { range, yes, fgm, context } => {
+                reword::execute_reword(range, context, fgm, yes).await
+            }
+            opencommit::cli::Commands::SquashMessage { base, apply, yes, fgm, context } => {
+                squash::execute_squash_message(base, apply, context, fgm, yes).await
+            }
+            opencommit::cli::Commands::Pr { base, template, output, context } => {
+                pr::execute_pr(base, template, output, context).await
+            }
         },
         None => {
             // Default command is commit
@@ -45,7 +79,8 @@ async fn main() -> Result<()> {
                 cli.context, 
                 false, 
                 cli.fgm, 
-                cli.yes
+                cli.yes,
+                cli.candidates
             ).await
         }
     }
@@ -14,7 +14,7 @@ async fn migration_use_single_api_key_and_url() -> Result<()> {
         return Ok(());
     }
     
-    let config = Config::load()?;
+    let config = Config::load_global()?;
     
     // Get environment variables for different providers
     let mut api_key = None;
@@ -66,7 +66,7 @@ async fn migration_set_missing_default_values() -> Result<()> {
         return Ok(());
     }
     
-    let config = Config::load()?;
+    let config = Config::load_global()?;
     let default_config = Config::default();
     
     // Fields to check and set if missing
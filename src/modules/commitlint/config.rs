@@ -0,0 +1,337 @@
+use crate::error::{Error, Result};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::debug;
+use serde_json::{json, Map, Value};
+
+// Config files commitlint looks for, in the order it looks for them
+const CONFIG_FILES: &[&str] = &[
+    ".commitlintrc",
+    ".commitlintrc.json",
+    ".commitlintrc.yaml",
+    ".commitlintrc.yml",
+    ".commitlintrc.js",
+    ".commitlintrc.cjs",
+    ".commitlintrc.mjs",
+    ".commitlintrc.ts",
+    "commitlint.config.json",
+    "commitlint.config.js",
+    "commitlint.config.cjs",
+    "commitlint.config.mjs",
+    "commitlint.config.ts",
+];
+
+// Rules of @commitlint/config-conventional
+fn config_conventional() -> Value {
+    json!({
+        "rules": {
+            "body-leading-blank": [1, "always"],
+            "body-max-line-length": [2, "always", 100],
+            "footer-leading-blank": [1, "always"],
+            "footer-max-line-length": [2, "always", 100],
+            "header-max-length": [2, "always", 100],
+            "header-trim": [2, "always"],
+            "subject-case": [2, "never", ["sentence-case", "start-case", "pascal-case", "upper-case"]],
+            "subject-empty": [2, "never"],
+            "subject-full-stop": [2, "never", "."],
+            "type-case": [2, "always", "lower-case"],
+            "type-empty": [2, "never"],
+            "type-enum": [2, "always", [
+                "build", "chore", "ci", "docs", "feat", "fix",
+                "perf", "refactor", "revert", "style", "test"
+            ]]
+        }
+    })
+}
+
+// Get a preset bundled with the tool
+fn builtin_preset(name: &str) -> Option<Value> {
+    match name {
+        "@commitlint/config-conventional" | "config-conventional" => Some(config_conventional()),
+        _ => None,
+    }
+}
+
+// Find the commitlint config for the current directory, like commitlint does
+fn find_config_file() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+
+    for dir in cwd.ancestors() {
+        for name in CONFIG_FILES {
+            let path = dir.join(name);
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+
+        // package.json only counts when it has a commitlint key
+        let package_json = dir.join("package.json");
+        if package_json.is_file() {
+            if let Ok(Some(_)) = read_package_json(&package_json) {
+                return Some(package_json);
+            }
+        }
+
+        // Don't look outside of the repository
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+
+    None
+}
+
+fn read_package_json(path: &Path) -> Result<Option<Value>> {
+    let content = fs::read_to_string(path)?;
+    let package: Value = serde_json::from_str(&content)?;
+    Ok(package.get("commitlint").cloned())
+}
+
+// Parse a config file into JSON according to its format
+fn read_config_file(path: &Path) -> Result<Value> {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+
+    if file_name == "package.json" {
+        return read_package_json(path)?
+            .ok_or_else(|| Error::CommitlintError(format!("No commitlint key in {}", path.display())));
+    }
+
+    let content = fs::read_to_string(path)?;
+
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => Ok(serde_json::from_str(&content)?),
+        Some("yaml") | Some("yml") => parse_yaml(&content, path),
+        Some("js") | Some("cjs") | Some("mjs") | Some("ts") => parse_js_like(&content, path),
+        // .commitlintrc may hold either JSON or YAML
+        _ => serde_json::from_str(&content).or_else(|_| parse_yaml(&content, path)),
+    }
+}
+
+fn parse_yaml(content: &str, path: &Path) -> Result<Value> {
+    serde_yaml::from_str(content)
+        .map_err(|e| Error::CommitlintError(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+// Extract the exported object literal from a JavaScript or TypeScript config
+fn parse_js_like(content: &str, path: &Path) -> Result<Value> {
+    let unsupported = || Error::CommitlintError(format!(
+        "Could not read {}: only configs exporting a plain object literal are supported",
+        path.display()
+    ));
+
+    let export_start = ["module.exports", "export default"]
+        .iter()
+        .filter_map(|marker| content.find(marker).map(|i| i + marker.len()))
+        .min()
+        .ok_or_else(unsupported)?;
+
+    let exported = content[export_start..].trim_start().trim_start_matches('=').trim_start();
+
+    // `export default config` refers to an object declared earlier in the file
+    let object_start = if exported.starts_with('{') {
+        export_start + content[export_start..].find('{').ok_or_else(unsupported)?
+    } else {
+        let ident: String = exported.chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+            .collect();
+        if ident.is_empty() {
+            return Err(unsupported());
+        }
+
+        let declaration = [format!("const {}", ident), format!("let {}", ident), format!("var {}", ident)]
+            .iter()
+            .filter_map(|d| content.find(d.as_str()))
+            .min()
+            .ok_or_else(unsupported)?;
+        let assignment = declaration + content[declaration..].find('=').ok_or_else(unsupported)?;
+        assignment + content[assignment..].find('{').ok_or_else(unsupported)?
+    };
+
+    let object_end = find_matching_brace(content, object_start).ok_or_else(unsupported)?;
+
+    // Severity enums are the only non-literal values common in these configs
+    let literal = content[object_start..=object_end]
+        .replace("RuleConfigSeverity.Disabled", "0")
+        .replace("RuleConfigSeverity.Warning", "1")
+        .replace("RuleConfigSeverity.Error", "2");
+
+    json5::from_str(&literal)
+        .map_err(|e| Error::CommitlintError(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+// Find the brace closing the one at `start`, skipping strings and comments
+fn find_matching_brace(content: &str, start: usize) -> Option<usize> {
+    let bytes = content.as_bytes();
+    let mut depth = 0;
+    let mut i = start;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' | b'`' => {
+                let quote = bytes[i];
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i + 1 < bytes.len() && !(bytes[i] == b'*' && bytes[i + 1] == b'/') {
+                    i += 1;
+                }
+                i += 1;
+            }
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    None
+}
+
+// Resolve `extends` and merge the rules, later configs overriding earlier ones,
+// `chain` holds the canonical paths of the files being resolved to catch cycles
+fn resolve_config(config: Value, base_dir: &Path, chain: &mut Vec<PathBuf>) -> Result<Value> {
+    let mut config = match config {
+        Value::Object(map) => map,
+        _ => return Err(Error::CommitlintError("commitlint config must be an object".to_string())),
+    };
+
+    let extends: Vec<String> = match config.get("extends") {
+        Some(Value::String(name)) => vec![name.clone()],
+        Some(Value::Array(names)) => names.iter().filter_map(|n| n.as_str().map(String::from)).collect(),
+        _ => Vec::new(),
+    };
+
+    let mut rules = Map::new();
+
+    for name in &extends {
+        let preset = if name.starts_with('.') {
+            let path = fs::canonicalize(base_dir.join(name))?;
+            if chain.contains(&path) {
+                return Err(Error::InvalidConfiguration(format!(
+                    "commitlint config {} extends itself",
+                    path.display()
+                )));
+            }
+
+            let parent = path.parent().unwrap_or(base_dir).to_path_buf();
+            chain.push(path.clone());
+            let preset = resolve_config(read_config_file(&path)?, &parent, chain);
+            chain.pop();
+            preset?
+        } else {
+            let preset = builtin_preset(name).ok_or_else(|| Error::CommitlintError(
+                format!("Unsupported commitlint preset: {}", name)
+            ))?;
+            resolve_config(preset, base_dir, chain)?
+        };
+
+        if let Some(Value::Object(preset_rules)) = preset.get("rules") {
+            rules.extend(preset_rules.clone());
+        }
+    }
+
+    if let Some(Value::Object(own_rules)) = config.get("rules") {
+        rules.extend(own_rules.clone());
+    }
+
+    config.insert("extends".to_string(), json!(extends));
+    config.insert("rules".to_string(), Value::Object(rules));
+
+    Ok(Value::Object(config))
+}
+
+// Load the project's commitlint config in the shape of `commitlint --print-config`
+pub fn load_commitlint_config() -> Result<Value> {
+    let path = find_config_file()
+        .ok_or_else(|| Error::CommitlintError("No commitlint configuration found".to_string()))?;
+
+    debug!("Loading commitlint configuration from {}", path.display());
+
+    let config = read_config_file(&path)?;
+    let base_dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+    resolve_config(config, &base_dir, &mut vec![fs::canonicalize(&path)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_object_literals_from_js_configs() {
+        let path = Path::new("commitlint.config.js");
+
+        let exported = "// lint\nmodule.exports = {\n  extends: ['@commitlint/config-conventional'],\n  rules: { 'header-max-length': [2, 'always', 72] }, // '}'\n};\n";
+        let config = parse_js_like(exported, path).unwrap();
+        assert_eq!(config["extends"], json!(["@commitlint/config-conventional"]));
+        assert_eq!(config["rules"]["header-max-length"], json!([2, "always", 72]));
+
+        let declared = "import { RuleConfigSeverity } from '@commitlint/types';\n\
+            const config = { rules: { 'body-empty': [RuleConfigSeverity.Warning, 'never'] } };\n\
+            export default config;\n";
+        assert_eq!(parse_js_like(declared, path).unwrap()["rules"]["body-empty"], json!([1, "never"]));
+
+        assert!(parse_js_like("module.exports = require('./base')", path).is_err());
+    }
+
+    #[test]
+    fn skips_braces_in_strings_and_comments() {
+        let content = "{ a: '}', /* } */ b: { c: \"}\" } // }\n}";
+        assert_eq!(find_matching_brace(content, 0), Some(content.len() - 1));
+    }
+
+    #[test]
+    fn own_rules_override_presets() {
+        let config = json!({
+            "extends": "@commitlint/config-conventional",
+            "rules": { "header-max-length": [2, "always", 72] }
+        });
+
+        let resolved = resolve_config(config, Path::new("."), &mut Vec::new()).unwrap();
+        assert_eq!(resolved["extends"], json!(["@commitlint/config-conventional"]));
+        assert_eq!(resolved["rules"]["header-max-length"], json!([2, "always", 72]));
+        assert_eq!(resolved["rules"]["type-empty"], json!([2, "never"]));
+
+        let unknown = json!({ "extends": ["@company/commitlint-config"] });
+        assert!(resolve_config(unknown, Path::new("."), &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn extends_cycles_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("base.json"), r#"{ "extends": "./shared/rules.json" }"#).unwrap();
+        fs::create_dir(dir.path().join("shared")).unwrap();
+        fs::write(
+            dir.path().join("shared/rules.json"),
+            r#"{ "extends": "../base.json", "rules": { "body-empty": [1, "never"] } }"#,
+        ).unwrap();
+
+        let config = json!({ "extends": ["./base.json"] });
+        let error = resolve_config(config, dir.path(), &mut Vec::new()).unwrap_err();
+        assert!(matches!(error, Error::InvalidConfiguration(_)), "unexpected error: {:?}", error);
+
+        // Extending the same file twice is not a cycle
+        fs::write(dir.path().join("base.json"), r#"{ "rules": { "body-empty": [2, "never"] } }"#).unwrap();
+        let config = json!({ "extends": ["./base.json", "./base.json"] });
+        let resolved = resolve_config(config, dir.path(), &mut Vec::new()).unwrap();
+        assert_eq!(resolved["rules"]["body-empty"], json!([2, "never"]));
+    }
+}
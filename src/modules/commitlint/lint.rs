@@ -0,0 +1,377 @@
+use serde_json::Value;
+use crate::modules::commitlint::parse::{trailer_value_start, CommitMessage};
+use crate::modules::commitlint::rules::{enabled_rules, matches_case, RuleConfig, RuleLevel};
+
+// A rule the commit message does not follow
+#[derive(Debug, Clone)]
+pub struct Violation {
+    pub rule: String,
+    pub level: RuleLevel,
+    pub message: String,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [{}]", self.message, self.rule)
+    }
+}
+
+// Check a commit message against the rules of a resolved commitlint config
+pub fn lint(message: &str, config: &Value) -> Vec<Violation> {
+    let commit = CommitMessage::parse(message);
+
+    enabled_rules(config)
+        .iter()
+        .filter_map(|rule| check_rule(&commit, message, rule).map(|text| Violation {
+            rule: rule.name.clone(),
+            level: rule.level,
+            message: text,
+        }))
+        .collect()
+}
+
+fn condition(rule: &RuleConfig) -> &'static str {
+    if rule.applicable { "must" } else { "must not" }
+}
+
+// Violation message for `always`/`never` rules, `holds` tells whether the message has the property
+fn expect(rule: &RuleConfig, holds: bool, description: String) -> Option<String> {
+    if holds == rule.applicable {
+        None
+    } else {
+        Some(format!("{} {}", condition(rule), description))
+    }
+}
+
+fn check_case(rule: &RuleConfig, part: &str, text: Option<&str>) -> Option<String> {
+    let text = text.filter(|t| !t.is_empty())?;
+    let cases = rule.value_list();
+    let holds = cases.iter().any(|case| matches_case(text, case));
+    expect(rule, holds, format!("be in {}", cases.join(" or "))).map(|m| format!("{} {}", part, m))
+}
+
+// Length rules ignore the condition, like commitlint does
+fn check_max_length(rule: &RuleConfig, part: &str, text: Option<&str>) -> Option<String> {
+    let (text, max) = (text?, rule.value_usize()?);
+    let length = text.chars().count();
+    (length > max).then(|| format!("{} must not be longer than {} characters, current length is {}", part, max, length))
+}
+
+fn check_min_length(rule: &RuleConfig, part: &str, text: Option<&str>) -> Option<String> {
+    let (text, min) = (text?, rule.value_usize()?);
+    let length = text.chars().count();
+    (length < min).then(|| format!("{} must not be shorter than {} characters, current length is {}", part, min, length))
+}
+
+fn check_max_line_length(rule: &RuleConfig, part: &str, text: Option<&str>) -> Option<String> {
+    let (text, max) = (text?, rule.value_usize()?);
+    text.lines()
+        .any(|line| line.chars().count() > max)
+        .then(|| format!("{}'s lines must not be longer than {} characters", part, max))
+}
+
+fn check_full_stop(rule: &RuleConfig, part: &str, text: Option<&str>) -> Option<String> {
+    let text = text.filter(|t| !t.is_empty())?;
+    let stop = rule.value_str().unwrap_or(".");
+    expect(rule, text.ends_with(stop), format!("end with '{}'", stop)).map(|m| format!("{} {}", part, m))
+}
+
+fn check_empty(rule: &RuleConfig, part: &str, text: Option<&str>) -> Option<String> {
+    let empty = text.map(|t| t.trim().is_empty()).unwrap_or(true);
+    expect(rule, empty, "be empty".to_string()).map(|m| format!("{} {}", part, m))
+}
+
+fn check_rule(commit: &CommitMessage, message: &str, rule: &RuleConfig) -> Option<String> {
+    let header = Some(commit.header.as_str());
+    let commit_type = commit.commit_type.as_deref();
+    let scope = commit.scope.as_deref();
+    let subject = commit.subject.as_deref();
+    let body = commit.body.as_deref();
+    let footer = commit.footer.as_deref();
+
+    match rule.name.as_str() {
+        "header-case" => check_case(rule, "header", header),
+        "header-full-stop" => check_full_stop(rule, "header", header),
+        "header-max-length" => check_max_length(rule, "header", header),
+        "header-min-length" => check_min_length(rule, "header", header),
+        "header-trim" => (commit.header != commit.header.trim())
+            .then(|| "header must not be surrounded by whitespace".to_string()),
+
+        "type-enum" => {
+            let commit_type = commit_type?;
+            let types = rule.value_list();
+            expect(rule, types.iter().any(|t| t == commit_type), format!("be one of [{}]", types.join(", ")))
+                .map(|m| format!("type {}", m))
+        }
+        "type-case" => check_case(rule, "type", commit_type),
+        "type-empty" => check_empty(rule, "type", commit_type),
+        "type-max-length" => check_max_length(rule, "type", commit_type),
+        "type-min-length" => check_min_length(rule, "type", commit_type),
+
+        "scope-enum" => {
+            let scope = scope.filter(|s| !s.is_empty())?;
+            let scopes = rule.value_list();
+            let holds = scope.split([',', '/'])
+                .all(|s| scopes.iter().any(|allowed| allowed == s.trim()));
+            expect(rule, holds, format!("be one of [{}]", scopes.join(", ")))
+                .map(|m| format!("scope {}", m))
+        }
+        "scope-case" => check_case(rule, "scope", scope),
+        "scope-empty" => check_empty(rule, "scope", scope),
+        "scope-max-length" => check_max_length(rule, "scope", scope),
+        "scope-min-length" => check_min_length(rule, "scope", scope),
+
+        "subject-case" => check_case(rule, "subject", subject),
+        "subject-empty" => check_empty(rule, "subject", subject),
+        "subject-full-stop" => check_full_stop(rule, "subject", subject),
+        "subject-max-length" => check_max_length(rule, "subject", subject),
+        "subject-min-length" => check_min_length(rule, "subject", subject),
+        "subject-exclamation-mark" => {
+            commit_type?;
+            expect(rule, commit.breaking, "have an exclamation mark before the colon".to_string())
+                .map(|m| format!("header {}", m))
+        }
+
+        "body-leading-blank" => {
+            body?;
+            expect(rule, commit.body_leading_blank, "have a leading blank line".to_string())
+                .map(|m| format!("body {}", m))
+        }
+        "body-empty" => check_empty(rule, "body", body),
+        "body-case" => check_case(rule, "body", body),
+        "body-full-stop" => check_full_stop(rule, "body", body),
+        "body-max-length" => check_max_length(rule, "body", body),
+        "body-max-line-length" => check_max_line_length(rule, "body", body),
+        "body-min-length" => check_min_length(rule, "body", Some(body.unwrap_or_default())),
+
+        "footer-leading-blank" => {
+            footer?;
+            expect(rule, commit.footer_leading_blank, "have a leading blank line".to_string())
+                .map(|m| format!("footer {}", m))
+        }
+        "footer-empty" => check_empty(rule, "footer", footer),
+        "footer-max-length" => check_max_length(rule, "footer", footer),
+        "footer-max-line-length" => check_max_line_length(rule, "footer", footer),
+        "footer-min-length" => check_min_length(rule, "footer", Some(footer.unwrap_or_default())),
+
+        "references-empty" => expect(rule, commit.references().is_empty(), "be empty".to_string())
+            .map(|m| format!("references {}", m)),
+        "signed-off-by" => {
+            let trailer = rule.value_str().unwrap_or("Signed-off-by:");
+            let last_line = message.trim_end().lines().rev().find(|l| !l.starts_with('#')).unwrap_or_default();
+            expect(rule, last_line.starts_with(trailer), format!("have '{}' as the last line", trailer))
+                .map(|m| format!("message {}", m))
+        }
+        "trailer-exists" => {
+            let trailer = rule.value_str()?;
+            let holds = footer.map(|f| f.lines().any(|l| l.starts_with(trailer))).unwrap_or(false);
+            expect(rule, holds, format!("have a '{}' trailer", trailer))
+                .map(|m| format!("message {}", m))
+        }
+
+        _ => None,
+    }
+}
+
+// Deterministically fix violations that don't need the model, like a trailing full stop
+pub fn fix(message: &str, config: &Value) -> String {
+    let mut commit = CommitMessage::parse(message.trim());
+    let rules = enabled_rules(config);
+    let rule = |name: &str| rules.iter().find(|r| r.name == name);
+
+    commit.header = commit.header.trim_end().to_string();
+
+    if let (Some(rule), Some(commit_type)) = (rule("type-case"), commit.commit_type.as_mut()) {
+        if rule.applicable && rule.value_list().iter().any(|c| c == "lower-case") {
+            *commit_type = commit_type.to_lowercase();
+        }
+    }
+
+    if let (Some(rule), Some(subject)) = (rule("subject-full-stop"), commit.subject.as_mut()) {
+        if !rule.applicable {
+            let stop = rule.value_str().unwrap_or(".");
+            while !stop.is_empty() && subject.ends_with(stop) {
+                subject.truncate(subject.len() - stop.len());
+            }
+        }
+    }
+
+    // Lowercasing the first letter is the usual fix for subject-case, keep it only if it works
+    if let (Some(rule), Some(subject)) = (rule("subject-case"), commit.subject.as_mut()) {
+        let mut lowered = subject.clone();
+        if let Some(first) = lowered.chars().next() {
+            lowered.replace_range(..first.len_utf8(), &first.to_lowercase().to_string());
+        }
+        let cases = rule.value_list();
+        let holds = |text: &str| cases.iter().any(|c| matches_case(text, c));
+        if holds(subject) != rule.applicable && holds(&lowered) == rule.applicable {
+            *subject = lowered;
+        }
+    }
+
+    // Rebuild the header from its fixed parts
+    if let (Some(commit_type), Some(subject)) = (&commit.commit_type, &commit.subject) {
+        commit.header = format!(
+            "{}{}{}: {}",
+            commit_type,
+            commit.scope.as_ref().map(|s| format!("({})", s)).unwrap_or_default(),
+            if commit.breaking { "!" } else { "" },
+            subject
+        );
+    }
+
+    if let Some(max) = rule("body-max-line-length").and_then(|r| r.value_usize()) {
+        commit.body = commit.body.map(|body| wrap_lines(&body, max));
+    }
+
+    if let Some(max) = rule("footer-max-line-length").and_then(|r| r.value_usize()) {
+        commit.footer = commit.footer.map(|footer| wrap_footer(&footer, max));
+    }
+
+    // Rebuilding the message restores the blank lines before body and footer
+    commit.to_message()
+}
+
+// Wrap long lines at word boundaries, words longer than the limit (like URLs) stay whole
+fn wrap_lines(text: &str, max: usize) -> String {
+    let mut wrapped = Vec::new();
+
+    for line in text.lines() {
+        if line.chars().count() <= max {
+            wrapped.push(line.to_string());
+            continue;
+        }
+
+        // Continuation lines keep the indentation, and line up with the text of list items
+        let indent: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+        let is_list_item = line.trim_start().starts_with("- ") || line.trim_start().starts_with("* ");
+        let continuation = if is_list_item { format!("{}  ", indent) } else { indent.clone() };
+
+        wrap_words(line.split_whitespace(), &indent, &continuation, max, &mut wrapped);
+    }
+
+    wrapped.join("\n")
+}
+
+// Wrap a footer without breaking its trailers apart: the token stays with the first word of its value
+// and the rest of the value goes on indented lines, which git and commitlint read as the same trailer
+fn wrap_footer(footer: &str, max: usize) -> String {
+    let mut wrapped = Vec::new();
+
+    for line in footer.lines() {
+        let Some(value_start) = trailer_value_start(line).filter(|_| line.chars().count() > max) else {
+            wrapped.push(wrap_lines(line, max));
+            continue;
+        };
+
+        let value = &line[value_start..];
+        let first_word_end = value.len() - value.trim_start().len()
+            + value.trim_start().find(char::is_whitespace).unwrap_or(value.trim_start().len());
+        let (head, rest) = line.split_at(value_start + first_word_end);
+
+        wrap_words(std::iter::once(head).chain(rest.split_whitespace()), "", "  ", max, &mut wrapped);
+    }
+
+    wrapped.join("\n")
+}
+
+// Lay the words out in lines of at most `max` characters where possible
+fn wrap_words<'a>(words: impl Iterator<Item = &'a str>, indent: &str, continuation: &str, max: usize, wrapped: &mut Vec<String>) {
+    let mut current = String::new();
+
+    for word in words {
+        if current.trim().is_empty() {
+            if current.is_empty() {
+                current.push_str(indent);
+            }
+        } else if current.chars().count() + 1 + word.chars().count() > max {
+            wrapped.push(std::mem::replace(&mut current, continuation.to_string()));
+        } else {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+
+    if !current.trim().is_empty() {
+        wrapped.push(current);
+    }
+}
+
+// Violations that make commitlint reject the commit
+pub fn errors(violations: &[Violation]) -> Vec<&Violation> {
+    violations.iter().filter(|v| v.level == RuleLevel::Error).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn conventional() -> Value {
+        json!({
+            "rules": {
+                "body-max-line-length": [2, "always", 20],
+                "header-max-length": [2, "always", 40],
+                "subject-case": [2, "never", ["sentence-case", "start-case", "pascal-case", "upper-case"]],
+                "subject-full-stop": [2, "never", "."],
+                "type-case": [2, "always", "lower-case"],
+                "type-enum": [2, "always", ["feat", "fix"]],
+                "footer-leading-blank": [1, "always"],
+                "scope-empty": [0, "never"]
+            }
+        })
+    }
+
+    fn rules_broken(message: &str) -> Vec<String> {
+        let mut rules: Vec<String> = lint(message, &conventional()).into_iter().map(|v| v.rule).collect();
+        rules.sort();
+        rules
+    }
+
+    #[test]
+    fn accepts_a_conforming_message() {
+        assert!(rules_broken("fix(parser): handle empty input\n\nShort lines only").is_empty());
+    }
+
+    #[test]
+    fn reports_broken_rules() {
+        assert_eq!(rules_broken("Docs: Update the readme."), ["subject-case", "subject-full-stop", "type-case", "type-enum"]);
+        assert_eq!(rules_broken("fix: a header that is much too long to be kept"), ["header-max-length"]);
+        assert_eq!(rules_broken("fix: wrap\n\nthis body line is longer than twenty"), ["body-max-line-length"]);
+    }
+
+    #[test]
+    fn levels_decide_what_is_an_error() {
+        let violations = lint("fix: a\nRefs #1", &conventional());
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].level, RuleLevel::Warning);
+        assert!(errors(&violations).is_empty());
+    }
+
+    #[test]
+    fn fixes_what_needs_no_model() {
+        let fixed = fix("Fix(api): Retry requests.\nthis body line is longer than twenty", &conventional());
+        assert_eq!(fixed, "fix(api): retry requests\n\nthis body line is\nlonger than twenty");
+        assert!(errors(&lint(&fixed, &conventional())).is_empty());
+    }
+
+    #[test]
+    fn wraps_footers_without_breaking_trailers() {
+        let config = json!({ "rules": { "footer-max-line-length": [2, "always", 24] } });
+        let message = "feat: a\n\nBody\n\nBREAKING CHANGE: the config file moved out of the repository\nRefs: #12 #34 #56 #78 #90 #91";
+        let fixed = fix(message, &config);
+
+        let commit = CommitMessage::parse(&fixed);
+        assert_eq!(commit.body.as_deref(), Some("Body"));
+        assert_eq!(commit.footer.as_deref(), Some(
+            "BREAKING CHANGE: the\n  config file moved out\n  of the repository\nRefs: #12 #34 #56 #78\n  #90 #91"
+        ));
+        assert!(lint(&fixed, &config).is_empty());
+    }
+
+    #[test]
+    fn wraps_list_items_under_their_text() {
+        assert_eq!(wrap_lines("- one two three four", 10), "- one two\n  three\n  four");
+        assert_eq!(wrap_lines("https://example.com/long", 10), "https://example.com/long");
+    }
+}
@@ -0,0 +1,8 @@
+pub mod config;
+pub mod rules;
+pub mod parse;
+pub mod lint;
+pub mod prompts;
+
+pub use config::load_commitlint_config;
+pub use lint::{lint, fix, Violation};
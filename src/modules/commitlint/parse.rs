@@ -0,0 +1,229 @@
+// A commit message split the way conventional-commits-parser splits it
+#[derive(Debug, Clone, Default)]
+pub struct CommitMessage {
+    pub header: String,
+    pub commit_type: Option<String>,
+    pub scope: Option<String>,
+    pub subject: Option<String>,
+    pub breaking: bool,
+    pub body: Option<String>,
+    pub footer: Option<String>,
+    // Whether a blank line separates the header from the body
+    pub body_leading_blank: bool,
+    // Whether a blank line separates the body (or header) from the footer
+    pub footer_leading_blank: bool,
+}
+
+impl CommitMessage {
+    pub fn parse(message: &str) -> Self {
+        let lines: Vec<&str> = message.trim_end().lines().collect();
+        let Some(header) = lines.first() else {
+            return Self::default();
+        };
+
+        let mut commit = Self {
+            header: header.to_string(),
+            ..Self::default()
+        };
+        commit.parse_header();
+
+        let rest = &lines[1..];
+        if rest.is_empty() {
+            return commit;
+        }
+
+        let footer_start = find_footer_start(rest);
+        let body_lines = &rest[..footer_start.unwrap_or(rest.len())];
+
+        commit.body_leading_blank = rest.first().map(|l| l.trim().is_empty()).unwrap_or(true);
+        commit.body = join_trimmed(body_lines);
+
+        if let Some(start) = footer_start {
+            commit.footer_leading_blank = start > 0 && rest[start - 1].trim().is_empty();
+            commit.footer = join_trimmed(&rest[start..]);
+        }
+
+        commit
+    }
+
+    // Split `type(scope)!: subject`
+    fn parse_header(&mut self) {
+        let Some((prefix, subject)) = self.header.split_once(':') else {
+            return;
+        };
+
+        let (prefix, breaking) = match prefix.strip_suffix('!') {
+            Some(prefix) => (prefix, true),
+            None => (prefix, false),
+        };
+
+        let (commit_type, scope) = match prefix.split_once('(') {
+            Some((commit_type, scope)) => match scope.strip_suffix(')') {
+                Some(scope) => (commit_type, Some(scope)),
+                None => return,
+            },
+            None => (prefix, None),
+        };
+
+        // A type is a single word, anything else is a free-form header
+        if commit_type.is_empty() || !commit_type.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+            return;
+        }
+
+        self.commit_type = Some(commit_type.to_string());
+        self.scope = scope.map(String::from);
+        self.subject = Some(subject.trim_start().to_string());
+        self.breaking = breaking;
+    }
+
+    // Issue references like `#123` anywhere in the message
+    pub fn references(&self) -> Vec<String> {
+        let text = [Some(&self.header), self.body.as_ref(), self.footer.as_ref()]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        text.split(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == ',')
+            .filter(|word| word.len() > 1 && word.starts_with('#') && word[1..].chars().all(|c| c.is_ascii_digit()))
+            .map(String::from)
+            .collect()
+    }
+
+    // Put the message back together with the conventional blank lines
+    pub fn to_message(&self) -> String {
+        let mut message = self.header.clone();
+
+        if let Some(body) = &self.body {
+            message.push_str("\n\n");
+            message.push_str(body);
+        }
+
+        if let Some(footer) = &self.footer {
+            message.push_str("\n\n");
+            message.push_str(footer);
+        }
+
+        message
+    }
+}
+
+fn is_breaking_change_line(line: &str) -> bool {
+    line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+}
+
+// A footer line is a `Token: value` or `Token #value` trailer, or a breaking change note
+pub fn is_trailer_line(line: &str) -> bool {
+    trailer_value_start(line).is_some()
+}
+
+// Where the value of a trailer starts, right after its token and separator
+pub fn trailer_value_start(line: &str) -> Option<usize> {
+    if is_breaking_change_line(line) {
+        return Some("BREAKING CHANGE:".len());
+    }
+
+    let (token_end, value_start) = match (line.find(": "), line.find(" #")) {
+        (Some(a), Some(b)) if b < a => (b, b + 1),
+        (Some(a), _) => (a, a + 2),
+        (None, Some(b)) => (b, b + 1),
+        (None, None) => return None,
+    };
+
+    let token = &line[..token_end];
+    (!token.is_empty() && token.chars().all(|c| c.is_alphanumeric() || c == '-')).then_some(value_start)
+}
+
+// Like git, a paragraph is a footer only when every line is a trailer or continues one,
+// breaking change notes may go on in free text
+fn is_footer_paragraph(lines: &[&str]) -> bool {
+    let Some(first) = lines.first() else {
+        return false;
+    };
+    if !is_trailer_line(first) {
+        return false;
+    }
+
+    let mut in_breaking_change = false;
+    for line in lines {
+        if is_trailer_line(line) {
+            in_breaking_change = is_breaking_change_line(line);
+        } else if !in_breaking_change && !line.starts_with([' ', '\t']) {
+            return false;
+        }
+    }
+
+    true
+}
+
+// The footer is the last paragraph, if it is made of trailers
+fn find_footer_start(lines: &[&str]) -> Option<usize> {
+    let paragraph_start = lines.iter()
+        .rposition(|l| l.trim().is_empty())
+        .map(|i| i + 1)
+        .unwrap_or(0);
+
+    is_footer_paragraph(&lines[paragraph_start..]).then_some(paragraph_start)
+}
+
+fn join_trimmed(lines: &[&str]) -> Option<String> {
+    let text = lines.join("\n").trim_matches('\n').to_string();
+    if text.trim().is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_header() {
+        let commit = CommitMessage::parse("feat(parser)!: add trailers");
+        assert_eq!(commit.commit_type.as_deref(), Some("feat"));
+        assert_eq!(commit.scope.as_deref(), Some("parser"));
+        assert_eq!(commit.subject.as_deref(), Some("add trailers"));
+        assert!(commit.breaking);
+
+        let free_form = CommitMessage::parse("Merge branch 'main': sync");
+        assert_eq!(free_form.commit_type, None);
+        assert_eq!(free_form.subject, None);
+    }
+
+    #[test]
+    fn splits_body_and_footer() {
+        let commit = CommitMessage::parse("fix: a\n\nBody text\n\nRefs #12\nReviewed-by: Someone\n  on two lines");
+        assert_eq!(commit.body.as_deref(), Some("Body text"));
+        assert_eq!(commit.footer.as_deref(), Some("Refs #12\nReviewed-by: Someone\n  on two lines"));
+        assert!(commit.body_leading_blank);
+        assert!(commit.footer_leading_blank);
+        assert_eq!(commit.references(), ["#12"]);
+    }
+
+    #[test]
+    fn keeps_prose_in_the_body() {
+        // Starts like a trailer, but the other lines are not
+        let commit = CommitMessage::parse("fix: a\n\nNote: the cache is cleared\nwhen the config changes");
+        assert_eq!(commit.body.as_deref(), Some("Note: the cache is cleared\nwhen the config changes"));
+        assert_eq!(commit.footer, None);
+
+        let commit = CommitMessage::parse("fix: a\n\nSee the notes: they explain it");
+        assert_eq!(commit.footer, None);
+    }
+
+    #[test]
+    fn breaking_change_notes_are_footers() {
+        let commit = CommitMessage::parse("feat: a\n\nBREAKING CHANGE: the config moved\nand is read once\nCloses #3");
+        assert_eq!(commit.body, None);
+        assert_eq!(commit.footer.as_deref(), Some("BREAKING CHANGE: the config moved\nand is read once\nCloses #3"));
+    }
+
+    #[test]
+    fn round_trips_the_message() {
+        let message = "fix(api): retry\n\nBody\n\nSigned-off-by: A <a@example.com>";
+        assert_eq!(CommitMessage::parse(message).to_message(), message);
+    }
+}
@@ -0,0 +1,192 @@
+use serde_json::Value;
+use crate::modules::commitlint::rules::{enabled_rules, RuleConfig};
+
+fn must(rule: &RuleConfig) -> &'static str {
+    if rule.applicable { "must" } else { "must not" }
+}
+
+// Join values as "a, b or c"
+fn join_values(values: &[String], last_separator: &str) -> String {
+    match values.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} {} {}", rest.join(", "), last_separator, last),
+        Some((last, _)) => last.clone(),
+        None => String::new(),
+    }
+}
+
+fn case_prompt(rule: &RuleConfig, part: &str) -> String {
+    let cases = rule.value_list();
+    if cases.is_empty() {
+        format!("The {} {} follow the configured case.", part, must(rule))
+    } else if rule.applicable {
+        format!("The {} must be in {}.", part, join_values(&cases, "or"))
+    } else {
+        format!("The {} must not be in {}.", part, join_values(&cases, "or"))
+    }
+}
+
+fn enum_prompt(rule: &RuleConfig, part: &str) -> String {
+    let values = rule.value_list();
+    if values.is_empty() {
+        format!("The {} {} be one of the allowed values.", part, must(rule))
+    } else {
+        format!("The {} {} be one of the following: {}.", part, must(rule), values.join(", "))
+    }
+}
+
+fn empty_prompt(rule: &RuleConfig, part: &str) -> String {
+    if rule.applicable {
+        format!("The {} must be empty.", part)
+    } else {
+        format!("The {} must not be empty.", part)
+    }
+}
+
+fn full_stop_prompt(rule: &RuleConfig, part: &str) -> String {
+    let stop = rule.value_str().unwrap_or(".");
+    format!("The {} {} end with '{}'.", part, must(rule), stop)
+}
+
+// Length rules ignore the condition in commitlint
+fn max_length_prompt(rule: &RuleConfig, part: &str) -> Option<String> {
+    rule.value_usize().map(|max| format!("The {} must not be longer than {} characters.", part, max))
+}
+
+fn min_length_prompt(rule: &RuleConfig, part: &str) -> Option<String> {
+    rule.value_usize().map(|min| format!("The {} must be at least {} characters long.", part, min))
+}
+
+fn max_line_length_prompt(rule: &RuleConfig, part: &str) -> Option<String> {
+    rule.value_usize().map(|max| format!("Each line of the {} must not be longer than {} characters.", part, max))
+}
+
+fn leading_blank_prompt(rule: &RuleConfig, part: &str) -> String {
+    format!("The {} {} begin with a blank line.", part, must(rule))
+}
+
+// Describe a commitlint rule in a way the model can follow
+pub fn rule_to_prompt(rule: &RuleConfig) -> Option<String> {
+    let prompt = match rule.name.as_str() {
+        "header-case" => case_prompt(rule, "header"),
+        "header-full-stop" => full_stop_prompt(rule, "header"),
+        "header-max-length" => max_length_prompt(rule, "header")?,
+        "header-min-length" => min_length_prompt(rule, "header")?,
+        "header-trim" => "The header must not have leading or trailing whitespace.".to_string(),
+
+        "type-enum" => enum_prompt(rule, "type"),
+        "type-case" => case_prompt(rule, "type"),
+        "type-empty" => empty_prompt(rule, "type"),
+        "type-max-length" => max_length_prompt(rule, "type")?,
+        "type-min-length" => min_length_prompt(rule, "type")?,
+
+        "scope-enum" => enum_prompt(rule, "scope"),
+        "scope-case" => case_prompt(rule, "scope"),
+        "scope-empty" => empty_prompt(rule, "scope"),
+        "scope-max-length" => max_length_prompt(rule, "scope")?,
+        "scope-min-length" => min_length_prompt(rule, "scope")?,
+
+        "subject-case" => case_prompt(rule, "subject"),
+        "subject-empty" => empty_prompt(rule, "subject"),
+        "subject-full-stop" => full_stop_prompt(rule, "subject"),
+        "subject-max-length" => max_length_prompt(rule, "subject")?,
+        "subject-min-length" => min_length_prompt(rule, "subject")?,
+        "subject-exclamation-mark" => format!(
+            "The header {} have an exclamation mark before the colon to mark a breaking change.",
+            must(rule)
+        ),
+
+        "body-leading-blank" => leading_blank_prompt(rule, "body"),
+        "body-empty" => empty_prompt(rule, "body"),
+        "body-case" => case_prompt(rule, "body"),
+        "body-full-stop" => full_stop_prompt(rule, "body"),
+        "body-max-length" => max_length_prompt(rule, "body")?,
+        "body-max-line-length" => max_line_length_prompt(rule, "body")?,
+        "body-min-length" => min_length_prompt(rule, "body")?,
+
+        "footer-leading-blank" => leading_blank_prompt(rule, "footer"),
+        "footer-empty" => empty_prompt(rule, "footer"),
+        "footer-max-length" => max_length_prompt(rule, "footer")?,
+        "footer-max-line-length" => max_line_length_prompt(rule, "footer")?,
+        "footer-min-length" => min_length_prompt(rule, "footer")?,
+
+        "references-empty" => if rule.applicable {
+            "The commit message must not reference any issues.".to_string()
+        } else {
+            "The commit message must reference at least one issue, like #123.".to_string()
+        },
+        "signed-off-by" => format!(
+            "The last line of the commit message {} be a '{}' trailer.",
+            must(rule),
+            rule.value_str().unwrap_or("Signed-off-by:")
+        ),
+        "trailer-exists" => format!(
+            "The footer {} contain a '{}' trailer.",
+            must(rule),
+            rule.value_str()?
+        ),
+
+        _ => match &rule.value {
+            Some(value) => format!("The commit message {} satisfy the '{}' rule with value {}.", must(rule), rule.name, value),
+            None => format!("The commit message {} satisfy the '{}' rule.", must(rule), rule.name),
+        },
+    };
+
+    Some(prompt)
+}
+
+// Infer prompts from commitlint config
+pub fn infer_prompts_from_commitlint_config(config: &Value) -> Vec<String> {
+    enabled_rules(config)
+        .iter()
+        .filter_map(rule_to_prompt)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn prompt(name: &str, entry: Value) -> Option<String> {
+        rule_to_prompt(&RuleConfig::parse(name, &entry).unwrap())
+    }
+
+    #[test]
+    fn describes_rules_with_their_condition() {
+        assert_eq!(
+            prompt("subject-case", json!([2, "never", ["sentence-case", "upper-case"]])).unwrap(),
+            "The subject must not be in sentence-case or upper-case."
+        );
+        assert_eq!(prompt("type-enum", json!([2, "always", ["feat", "fix"]])).unwrap(), "The type must be one of the following: feat, fix.");
+        assert_eq!(prompt("subject-full-stop", json!([2, "never", "."])).unwrap(), "The subject must not end with '.'.");
+        assert_eq!(prompt("body-leading-blank", json!([1, "always"])).unwrap(), "The body must begin with a blank line.");
+        assert_eq!(
+            prompt("references-empty", json!([2, "never"])).unwrap(),
+            "The commit message must reference at least one issue, like #123."
+        );
+    }
+
+    #[test]
+    fn length_rules_need_a_value() {
+        assert_eq!(prompt("header-max-length", json!([2, "always", 72])).unwrap(), "The header must not be longer than 72 characters.");
+        assert_eq!(prompt("header-max-length", json!([2, "always"])), None);
+        assert_eq!(prompt("trailer-exists", json!([2, "always"])), None);
+    }
+
+    #[test]
+    fn describes_unknown_rules_by_name() {
+        assert_eq!(
+            prompt("plugin-rule", json!([2, "always", 3])).unwrap(),
+            "The commit message must satisfy the 'plugin-rule' rule with value 3."
+        );
+    }
+
+    #[test]
+    fn infers_prompts_for_enabled_rules() {
+        let config = json!({ "rules": { "body-empty": [0, "never"], "header-trim": [2, "always"] } });
+        assert_eq!(
+            infer_prompts_from_commitlint_config(&config),
+            ["The header must not have leading or trailing whitespace."]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use serde_json::Value;
+
+// Severity of a commitlint rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RuleLevel {
+    Disabled,
+    Warning,
+    Error,
+}
+
+// A single `[level, condition, value]` entry of a commitlint config
+#[derive(Debug, Clone)]
+pub struct RuleConfig {
+    pub name: String,
+    pub level: RuleLevel,
+    // false for the "never" condition
+    pub applicable: bool,
+    pub value: Option<Value>,
+}
+
+impl RuleConfig {
+    pub fn parse(name: &str, config: &Value) -> Option<Self> {
+        let entry = config.as_array()?;
+
+        let level = match entry.first()?.as_i64()? {
+            1 => RuleLevel::Warning,
+            2 => RuleLevel::Error,
+            _ => RuleLevel::Disabled,
+        };
+
+        // Conditions are "always"/"never", older configs sometimes use booleans
+        let applicable = match entry.get(1) {
+            Some(Value::String(condition)) => condition != "never",
+            Some(Value::Bool(condition)) => *condition,
+            _ => true,
+        };
+
+        Some(Self {
+            name: name.to_string(),
+            level,
+            applicable,
+            value: entry.get(2).cloned(),
+        })
+    }
+
+    pub fn value_usize(&self) -> Option<usize> {
+        self.value.as_ref().and_then(|v| v.as_u64()).map(|v| v as usize)
+    }
+
+    pub fn value_str(&self) -> Option<&str> {
+        self.value.as_ref().and_then(|v| v.as_str())
+    }
+
+    // The value as a list, wrapping a single string
+    pub fn value_list(&self) -> Vec<String> {
+        match &self.value {
+            Some(Value::Array(items)) => items.iter().filter_map(|i| i.as_str().map(String::from)).collect(),
+            Some(Value::String(item)) => vec![item.clone()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+// Enabled rules of a resolved commitlint config
+pub fn enabled_rules(config: &Value) -> Vec<RuleConfig> {
+    let Some(rules) = config.get("rules").and_then(|r| r.as_object()) else {
+        return Vec::new();
+    };
+
+    rules.iter()
+        .filter_map(|(name, rule)| RuleConfig::parse(name, rule))
+        .filter(|rule| rule.level != RuleLevel::Disabled)
+        .collect()
+}
+
+// Check a piece of text against a commitlint case name
+pub fn matches_case(text: &str, case: &str) -> bool {
+    let letters = || text.chars().filter(|c| c.is_alphabetic());
+
+    match case {
+        "lower-case" | "lowercase" => text == text.to_lowercase(),
+        "upper-case" | "uppercase" => text == text.to_uppercase(),
+        "sentence-case" | "sentencecase" => text.chars().next().map(|c| !c.is_lowercase()).unwrap_or(true),
+        "start-case" | "startcase" => text.split_whitespace()
+            .all(|word| word.chars().next().map(|c| !c.is_lowercase()).unwrap_or(true)),
+        "pascal-case" | "pascalcase" => !text.contains(|c: char| c.is_whitespace() || c == '-' || c == '_')
+            && text.chars().next().map(|c| c.is_uppercase()).unwrap_or(true),
+        "camel-case" | "camelcase" => !text.contains(|c: char| c.is_whitespace() || c == '-' || c == '_')
+            && text.chars().next().map(|c| !c.is_uppercase()).unwrap_or(true),
+        "kebab-case" | "kebabcase" => !text.contains(|c: char| c.is_whitespace() || c == '_')
+            && letters().all(|c| c.is_lowercase()),
+        "snake-case" | "snakecase" => !text.contains(|c: char| c.is_whitespace() || c == '-')
+            && letters().all(|c| c.is_lowercase()),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_rule_entries() {
+        let rule = RuleConfig::parse("subject-case", &json!([2, "never", ["upper-case"]])).unwrap();
+        assert_eq!(rule.level, RuleLevel::Error);
+        assert!(!rule.applicable);
+        assert_eq!(rule.value_list(), ["upper-case"]);
+
+        let rule = RuleConfig::parse("header-max-length", &json!([1, true, 72])).unwrap();
+        assert_eq!(rule.level, RuleLevel::Warning);
+        assert!(rule.applicable);
+        assert_eq!(rule.value_usize(), Some(72));
+
+        assert!(RuleConfig::parse("body-empty", &json!("always")).is_none());
+    }
+
+    #[test]
+    fn skips_disabled_rules() {
+        let config = json!({ "rules": { "body-empty": [0, "never"], "type-empty": [2, "never"] } });
+        let names: Vec<String> = enabled_rules(&config).into_iter().map(|r| r.name).collect();
+        assert_eq!(names, ["type-empty"]);
+        assert!(enabled_rules(&json!({})).is_empty());
+    }
+
+    #[test]
+    fn matches_cases() {
+        assert!(matches_case("add parser", "lower-case"));
+        assert!(!matches_case("Add parser", "lower-case"));
+        assert!(matches_case("Add parser", "sentence-case"));
+        assert!(matches_case("Add Parser", "start-case"));
+        assert!(matches_case("AddParser", "pascal-case"));
+        assert!(matches_case("addParser", "camel-case"));
+        assert!(matches_case("add-parser", "kebab-case"));
+        assert!(matches_case("add_parser", "snake-case"));
+        assert!(!matches_case("add parser", "snake-case"));
+    }
+}
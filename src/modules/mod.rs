@@ -0,0 +1,2 @@
+pub mod commitlint;
+pub mod secrets;
@@ -0,0 +1,253 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use dirs::home_dir;
+use log::debug;
+
+use crate::error::{Error, Result};
+
+// Service name the secrets are stored under in the OS keyring
+const KEYRING_SERVICE: &str = "opencommit";
+
+// Config values starting with this name a stored secret instead of holding it
+pub const SECRET_PREFIX: &str = "secret:";
+
+// Passphrase of the encrypted secrets file, for machines without a keyring like CI
+pub const PASSPHRASE_ENV: &str = "OCO_SECRETS_PASSPHRASE";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+// Where a secret is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretBackend {
+    Keyring,
+    File,
+}
+
+impl std::fmt::Display for SecretBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecretBackend::Keyring => write!(f, "the OS keyring"),
+            SecretBackend::File => write!(f, "{}", secrets_file_path().display()),
+        }
+    }
+}
+
+pub fn secrets_file_path() -> PathBuf {
+    home_dir().unwrap_or_default().join(".opencommit_secrets")
+}
+
+pub fn reference(name: &str) -> String {
+    format!("{}{}", SECRET_PREFIX, name)
+}
+
+// Name of the secret a config value refers to, if it is a reference
+pub fn parse_reference(value: &str) -> Option<&str> {
+    value.strip_prefix(SECRET_PREFIX)
+}
+
+// Store a secret in the keyring, falling back to the encrypted file when there is no keyring
+pub fn store(name: &str, secret: &str, backend: Option<SecretBackend>) -> Result<SecretBackend> {
+    if backend != Some(SecretBackend::File) {
+        match keyring_entry(name).and_then(|entry| entry.set_password(secret).map_err(keyring_error)) {
+            Ok(()) => return Ok(SecretBackend::Keyring),
+            Err(e) if backend == Some(SecretBackend::Keyring) => return Err(e),
+            Err(e) => debug!("Keyring unavailable, using the encrypted file: {}", e),
+        }
+    }
+
+    let mut secrets = read_secrets_file()?;
+    secrets.insert(name.to_string(), encrypt(secret, &passphrase()?)?);
+    write_secrets_file(&secrets)?;
+
+    Ok(SecretBackend::File)
+}
+
+// Look a secret up in the keyring, then in the encrypted file
+pub fn get(name: &str) -> Result<String> {
+    match keyring_entry(name).and_then(|entry| entry.get_password().map_err(keyring_error)) {
+        Ok(secret) => return Ok(secret),
+        Err(e) => debug!("Secret '{}' not found in keyring: {}", name, e),
+    }
+
+    let secrets = read_secrets_file()?;
+    let encrypted = secrets.get(name).ok_or_else(|| Error::SecretError(format!(
+        "No secret named '{}' in the OS keyring or {}",
+        name,
+        secrets_file_path().display()
+    )))?;
+
+    decrypt(encrypted, &passphrase()?)
+}
+
+// Remove a secret from wherever it is stored
+pub fn delete(name: &str) -> Result<()> {
+    let from_keyring = keyring_entry(name)
+        .and_then(|entry| entry.delete_credential().map_err(keyring_error))
+        .is_ok();
+
+    let mut secrets = read_secrets_file()?;
+    let from_file = secrets.remove(name).is_some();
+    if from_file {
+        write_secrets_file(&secrets)?;
+    }
+
+    if from_keyring || from_file {
+        Ok(())
+    } else {
+        Err(Error::SecretError(format!("No secret named '{}'", name)))
+    }
+}
+
+fn keyring_entry(name: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, name).map_err(keyring_error)
+}
+
+fn keyring_error(e: keyring::Error) -> Error {
+    Error::SecretError(format!("Keyring error: {}", e))
+}
+
+fn read_secrets_file() -> Result<BTreeMap<String, String>> {
+    let path = secrets_file_path();
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn write_secrets_file(secrets: &BTreeMap<String, String>) -> Result<()> {
+    write_secrets(&secrets_file_path(), secrets)
+}
+
+// Write to a private temporary file and move it into place, so the secrets are never readable by others
+fn write_secrets(path: &Path, secrets: &BTreeMap<String, String>) -> Result<()> {
+    let temporary = path.with_extension("tmp");
+
+    // A leftover temporary file may have other permissions, start from a new one
+    let _ = fs::remove_file(&temporary);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let result = options.open(&temporary)
+        .and_then(|mut file| {
+            file.write_all(serde_json::to_string_pretty(secrets)?.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temporary, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+
+    Ok(result?)
+}
+
+fn passphrase() -> Result<String> {
+    std::env::var(PASSPHRASE_ENV).map_err(|_| Error::SecretError(format!(
+        "No OS keyring available, set {} to use the encrypted secrets file",
+        PASSPHRASE_ENV
+    )))
+}
+
+fn file_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| Error::SecretError(format!("Failed to derive the secrets file key: {}", e)))?;
+
+    Ok(key)
+}
+
+// Encrypted secrets are stored as base64 of salt, nonce and ciphertext
+fn encrypt(secret: &str, passphrase: &str) -> Result<String> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+
+    let cipher = ChaCha20Poly1305::new(&file_key(passphrase, &salt)?);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, secret.as_bytes())
+        .map_err(|_| Error::SecretError("Failed to encrypt secret".to_string()))?;
+
+    Ok(STANDARD.encode([salt.as_slice(), nonce.as_slice(), &ciphertext].concat()))
+}
+
+fn decrypt(encrypted: &str, passphrase: &str) -> Result<String> {
+    let data = STANDARD.decode(encrypted)
+        .map_err(|e| Error::SecretError(format!("Corrupted secrets file: {}", e)))?;
+    if data.len() < SALT_LEN + NONCE_LEN {
+        return Err(Error::SecretError("Corrupted secrets file".to_string()));
+    }
+
+    let (salt, rest) = data.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let cipher = ChaCha20Poly1305::new(&file_key(passphrase, salt)?);
+    let secret = cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| Error::SecretError(format!("Failed to decrypt secret, is {} right?", PASSPHRASE_ENV)))?;
+
+    String::from_utf8(secret).map_err(|e| Error::SecretError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_name_secrets() {
+        assert_eq!(reference("openai"), "secret:openai");
+        assert_eq!(parse_reference("secret:openai"), Some("openai"));
+        assert_eq!(parse_reference("sk-123"), None);
+    }
+
+    #[test]
+    fn encrypted_secrets_round_trip() {
+        let encrypted = encrypt("sk-secret", "correct horse").unwrap();
+        assert!(!encrypted.contains("sk-secret"));
+        assert_eq!(decrypt(&encrypted, "correct horse").unwrap(), "sk-secret");
+
+        // Every secret gets its own salt and nonce
+        assert_ne!(encrypt("sk-secret", "correct horse").unwrap(), encrypted);
+
+        assert!(decrypt(&encrypted, "wrong").is_err());
+        assert!(decrypt("not base64!", "correct horse").is_err());
+    }
+
+    #[test]
+    fn secrets_file_is_private() {
+        let dir = std::env::temp_dir().join(format!("opencommit-secrets-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(".opencommit_secrets");
+
+        let secrets = BTreeMap::from([("openai".to_string(), "encrypted".to_string())]);
+        write_secrets(&path, &secrets).unwrap();
+
+        let content: BTreeMap<String, String> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(content, secrets);
+        assert!(!path.with_extension("tmp").exists());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,7 +1,9 @@
-use crate::commands::config::Config;
+use crate::commands::commitlint::load_commitlint_llm_config;
+use crate::commands::config::{Config, PromptModule};
 use crate::engine::engine::Message;
 use crate::error::Result;
 use crate::i18n::get_translation;
+use std::str::FromStr;
 
 // Identity for the AI assistant
 const IDENTITY: &str = "You are to act as an author of a commit message in git.";
@@ -127,10 +129,11 @@ import {
 // Get main prompt for commit message generation
 pub async fn get_main_commit_prompt(full_gitmoji_spec: bool, context: String) -> Result<Vec<Message>> {
     let config = Config::load()?;
-    let translation = get_translation(&config.language)?;
+    let mut translation = get_translation(&config.language)?;
+    let prompt_module = PromptModule::from_str(&config.prompt_module)?;
     
     // Determine emoji/convention guidance
-    let commit_convention = if config.emoji {
+    let emoji_convention = if config.emoji {
         if full_gitmoji_spec {
             FULL_GITMOJI_SPEC
         } else {
@@ -140,6 +143,26 @@ pub async fn get_main_commit_prompt(full_gitmoji_spec: bool, context: String) ->
         CONVENTIONAL_COMMIT_KEYWORDS
     };
     
+    let commit_convention = match prompt_module {
+        PromptModule::ConventionalCommit => emoji_convention.to_string(),
+        PromptModule::Commitlint => {
+            let commitlint_config = load_commitlint_llm_config().await?;
+            
+            // Use the examples generated for the commitlint rules instead of the built-in ones
+            if let Some(consistency) = commitlint_config.consistency_for(&translation.local_language) {
+                translation.commit_fix = consistency.commit_fix;
+                translation.commit_feat = consistency.commit_feat;
+                translation.commit_description = consistency.commit_description;
+            }
+            
+            format!(
+                "{}\nYou will strictly follow the following @commitlint conventions to generate the content of the commit message:\n- {}",
+                if config.emoji { "Use GitMoji convention to preface the commit." } else { "Do not preface the commit with anything." },
+                commitlint_config.prompts.join("\n- ")
+            )
+        }
+    };
+    
     // Determine description guidance
     let description_guidance = if config.description {
         "Add a short description of WHY the changes are done after the commit message. Don't start it with \"This commit\", just describe the changes."
@@ -1,5 +1,7 @@
+use std::cell::RefCell;
 use std::path::Path;
-use git2::{Repository, Status, StatusOptions};
+use std::rc::Rc;
+use git2::{ApplyOptions, BranchType, Commit, Delta, Diff, DiffFormat, DiffOptions, Patch, Repository, Sort, Status, StatusOptions, Tree};
 use crate::error::{Error, Result};
 use std::fs;
 use std::process::Command;
@@ -114,16 +116,7 @@ pub fn get_diff(repo: &Repository, files: &[String]) -> Result<String> {
     
     // Filter out lock files and binary files
     let filtered_files: Vec<_> = files.iter()
-        .filter(|file| {
-            !file.contains(".lock") && 
-            !file.contains("-lock.") &&
-            !file.contains(".svg") &&
-            !file.contains(".png") &&
-            !file.contains(".jpg") &&
-            !file.contains(".jpeg") &&
-            !file.contains(".webp") &&
-            !file.contains(".gif")
-        })
+        .filter(|file| !is_excluded_from_diff(file))
         .map(|s| s.as_str())
         .collect();
     
@@ -133,10 +126,553 @@ pub fn get_diff(repo: &Repository, files: &[String]) -> Result<String> {
     let output = Command::new("git")
         .args(&args)
         .output()?;
-        
+    
     if !output.status.success() {
         return Err(Error::Git(git2::Error::from_str(&String::from_utf8_lossy(&output.stderr))));
     }
     
     Ok(String::from_utf8_lossy(&output.stdout).to_string())
-}
\ No newline at end of file
+}
+
+// Commit the index through git so hooks and signing apply as usual
+pub fn git_commit(message: &str) -> Result<()> {
+    let output = Command::new("git")
+        .args(["commit", "--quiet", "-m", message])
+        .output()?;
+    
+    if !output.status.success() {
+        return Err(Error::Git(git2::Error::from_str(&format!(
+            "git commit failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))));
+    }
+    
+    Ok(())
+}
+
+// Lock files and images only add noise to the diff sent to the model
+fn is_excluded_from_diff(file: &str) -> bool {
+    file.contains(".lock") || 
+    file.contains("-lock.") ||
+    file.contains(".svg") ||
+    file.contains(".png") ||
+    file.contains(".jpg") ||
+    file.contains(".jpeg") ||
+    file.contains(".webp") ||
+    file.contains(".gif")
+}
+
+// Where the current branch forked off `base`
+pub fn get_merge_base<'r>(repo: &'r Repository, base: &str) -> Result<Commit<'r>> {
+    let head = repo.head()?.peel_to_commit()?;
+    let base = repo.revparse_single(base)?.peel_to_commit()?;
+    
+    Ok(repo.find_commit(repo.merge_base(base.id(), head.id())?)?)
+}
+
+// The branch others merge into: the remote's HEAD, then init.defaultBranch, then main or master
+pub fn get_default_branch(repo: &Repository) -> Result<String> {
+    if let Ok(reference) = repo.find_reference("refs/remotes/origin/HEAD") {
+        if let Some(target) = reference.symbolic_target() {
+            return Ok(target.trim_start_matches("refs/remotes/").to_string());
+        }
+    }
+    
+    let configured = repo.config()
+        .and_then(|config| config.get_string("init.defaultBranch"))
+        .ok();
+    
+    configured.into_iter()
+        .chain(["main".to_string(), "master".to_string()])
+        .find(|name| repo.find_branch(name, BranchType::Local).is_ok())
+        .ok_or_else(|| Error::Git(git2::Error::from_str("could not find the default branch, pass it with --base")))
+}
+
+// Commits reachable from HEAD but not from `since`, oldest first
+pub fn get_commits_since<'r>(repo: &'r Repository, since: &Commit) -> Result<Vec<Commit<'r>>> {
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    walk.push_head()?;
+    walk.hide(since.id())?;
+    
+    let mut commits = Vec::new();
+    for id in walk {
+        commits.push(repo.find_commit(id?)?);
+    }
+    
+    Ok(commits)
+}
+
+// Diff of the changes a commit introduced, against its first parent
+pub fn get_commit_diff(repo: &Repository, commit: &Commit) -> Result<String> {
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+    
+    get_tree_diff(repo, parent_tree.as_ref(), &commit.tree()?)
+}
+
+// Diff between two trees, in the same form as `get_diff`
+pub fn get_tree_diff(repo: &Repository, old_tree: Option<&Tree>, new_tree: &Tree) -> Result<String> {
+    let diff = repo.diff_tree_to_tree(old_tree, Some(new_tree), None)?;
+    let mut text = String::new();
+    
+    diff.print(DiffFormat::Patch, |delta, _, line| {
+        let path = delta.new_file().path().or_else(|| delta.old_file().path());
+        if path.is_some_and(|path| is_excluded_from_diff(&path.to_string_lossy())) {
+            return true;
+        }
+        
+        if let origin @ (' ' | '+' | '-') = line.origin() {
+            text.push(origin);
+        }
+        text.push_str(&String::from_utf8_lossy(line.content()));
+        true
+    })?;
+    
+    Ok(text)
+}
+
+// Let the user edit a commit message in their git editor, returning it cleaned up like git does
+pub fn edit_commit_message(repo: &Repository, message: &str) -> Result<String> {
+    let comment_char = repo.config()
+        .and_then(|config| config.get_string("core.commentChar"))
+        .ok()
+        .and_then(|value| value.chars().next().filter(|_| value.chars().count() == 1))
+        .unwrap_or('#');
+    
+    let path = repo.path().join("OCO_EDITMSG");
+    fs::write(&path, format!(
+        "{}\n\n{c} Please edit the commit message. Lines starting\n{c} with '{c}' will be ignored, and an empty message aborts the commit.\n",
+        message.trim_end(),
+        c = comment_char
+    ))?;
+    
+    // Resolves GIT_EDITOR, core.editor, VISUAL and EDITOR in the same order git does
+    let output = Command::new("git")
+        .args(["var", "GIT_EDITOR"])
+        .output()?;
+    
+    if !output.status.success() {
+        return Err(Error::Git(git2::Error::from_str(&String::from_utf8_lossy(&output.stderr))));
+    }
+    
+    let editor = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    
+    // Editors are shell commands that can carry arguments, like `code --wait`
+    let status = Command::new("sh")
+        .args(["-c", &format!("{} \"$@\"", editor), &editor])
+        .arg(&path)
+        .status()?;
+    
+    if !status.success() {
+        return Err(Error::Git(git2::Error::from_str(&format!("There was a problem with the editor '{}'", editor))));
+    }
+    
+    let edited = fs::read_to_string(&path)?;
+    let _ = fs::remove_file(&path);
+    
+    Ok(strip_commit_message(&edited, comment_char))
+}
+
+// Drop comment lines, trailing whitespace and repeated blank lines, as `git commit --cleanup=strip`
+pub fn strip_commit_message(message: &str, comment_char: char) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    
+    for line in message.lines() {
+        if line.starts_with(comment_char) {
+            continue;
+        }
+        
+        let line = line.trim_end();
+        if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        
+        lines.push(line);
+    }
+    
+    while lines.last() == Some(&"") {
+        lines.pop();
+    }
+    
+    lines.join("\n")
+}
+
+// A change that can be staged or committed on its own: a hunk, or a whole file for changes without hunks
+#[derive(Debug, Clone)]
+pub struct Hunk {
+    pub path: String,
+    // The `diff --git` lines of the file, up to its first hunk
+    pub file_header: String,
+    // The `@@` line, or a description for changes without hunks
+    pub header: String,
+    // Lines prefixed with their origin as in a unified diff, or the data of a binary patch
+    pub body: String,
+    // Old and new start lines, None for binary, mode-only and other changes without hunks
+    position: Option<(u32, u32)>,
+}
+
+impl Hunk {
+    // The hunk as a unified diff of its own file, readable by the model
+    pub fn to_diff(&self) -> String {
+        match self.position {
+            Some(_) => format!("{}{}{}", self.file_header, self.header, self.body),
+            None if !self.body.is_empty() => format!("{}Binary files differ\n", self.file_header),
+            None => self.file_header.clone(),
+        }
+    }
+    
+    // Lines added or removed by the hunk
+    pub fn changed_lines(&self) -> Vec<&str> {
+        self.body.lines()
+            .filter(|line| line.starts_with('+') || line.starts_with('-'))
+            .collect()
+    }
+    
+    // The hunk reduced to the given changed lines, the others are left as they are in the index
+    pub fn with_lines(&self, selected: &[usize]) -> Hunk {
+        let Some((old_start, new_start)) = self.position else {
+            return self.clone();
+        };
+        
+        let mut body = String::new();
+        let mut changed = 0;
+        let mut dropped = false;
+        
+        for line in self.body.lines() {
+            let (origin, content) = line.split_at(line.chars().next().map_or(0, char::len_utf8));
+            
+            match origin {
+                "+" | "-" => {
+                    let keep = selected.contains(&changed);
+                    changed += 1;
+                    dropped = false;
+                    
+                    match (origin, keep) {
+                        // An unselected addition is left out entirely
+                        ("+", false) => {
+                            dropped = true;
+                            continue;
+                        }
+                        // An unselected removal stays as context
+                        ("-", false) => body.push_str(&format!(" {}\n", content)),
+                        _ => body.push_str(&format!("{}\n", line)),
+                    }
+                }
+                // The no newline marker belongs to the line before it
+                "\\" if dropped => {}
+                _ => {
+                    dropped = false;
+                    body.push_str(&format!("{}\n", line));
+                }
+            }
+        }
+        
+        let mut hunk = Hunk {
+            path: self.path.clone(),
+            file_header: self.file_header.clone(),
+            header: self.header.clone(),
+            body,
+            position: self.position,
+        };
+        hunk.header = hunk.header_at(old_start, new_start);
+        hunk
+    }
+    
+    // Lines of the old and the new file the hunk spans
+    fn line_counts(&self) -> (u32, u32) {
+        let count = |origin: char| self.body.lines().filter(|l| l.starts_with(' ') || l.starts_with(origin)).count() as u32;
+        (count('-'), count('+'))
+    }
+    
+    // The `@@` line for the body at the given start lines, keeping the function name git shows after them
+    fn header_at(&self, old_start: u32, new_start: u32) -> String {
+        let (old_count, new_count) = self.line_counts();
+        let context = self.header.trim_end().splitn(3, "@@").nth(2).unwrap_or_default();
+        format!("@@ -{},{} +{},{} @@{}\n", old_start, old_count, new_start, new_count, context)
+    }
+    
+    // The `@@` line once the hunks staged before it in the same file moved its lines by `offset`
+    fn header_with_offset(&self, offset: i64) -> String {
+        let Some((old_start, _)) = self.position else {
+            return self.header.clone();
+        };
+        
+        // A side without lines starts at the line before the hunk
+        let (old_count, new_count) = self.line_counts();
+        let first_line = if old_count == 0 { i64::from(old_start) + 1 } else { i64::from(old_start) };
+        let new_start = if new_count == 0 { first_line + offset - 1 } else { first_line + offset };
+        
+        self.header_at(old_start, new_start.max(0) as u32)
+    }
+}
+
+// Diff of the staged changes against HEAD, empty tree for the first commit
+pub fn get_staged_diff(repo: &Repository) -> Result<Diff<'_>> {
+    let head_tree = head_tree(repo)?;
+    let mut opts = DiffOptions::new();
+    opts.show_binary(true);
+    
+    Ok(repo.diff_tree_to_index(head_tree.as_ref(), None, Some(&mut opts))?)
+}
+
+// Diff of the unstaged changes, untracked files included
+pub fn get_unstaged_diff(repo: &Repository) -> Result<Diff<'_>> {
+    let mut opts = DiffOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true)
+        .show_binary(true);
+    
+    Ok(repo.diff_index_to_workdir(None, Some(&mut opts))?)
+}
+
+pub fn head_tree(repo: &Repository) -> Result<Option<Tree<'_>>> {
+    match repo.head() {
+        Ok(head) => Ok(Some(head.peel_to_tree()?)),
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch || e.code() == git2::ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Split a diff into the hunks of each file
+pub fn get_diff_hunks(diff: &Diff) -> Result<Vec<Hunk>> {
+    let mut hunks = Vec::new();
+    
+    for index in 0..diff.deltas().len() {
+        let Some(mut patch) = Patch::from_diff(diff, index)? else {
+            continue;
+        };
+        
+        let delta = patch.delta();
+        let path = delta.new_file().path()
+            .or_else(|| delta.old_file().path())
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let status = delta.status();
+        let binary = delta.flags().is_binary();
+        
+        // Everything before the first hunk, or before the data of a binary patch
+        let text = patch.to_buf()?;
+        let text = String::from_utf8_lossy(&text);
+        let header_end = text.find("\n@@ ")
+            .or_else(|| text.find("\nGIT binary patch"))
+            .map_or(text.len(), |end| end + 1);
+        let (file_header, rest) = text.split_at(header_end);
+        
+        if patch.num_hunks() == 0 || binary {
+            let header = match status {
+                Delta::Added | Delta::Untracked => "new file\n",
+                Delta::Deleted => "deleted file\n",
+                Delta::Renamed => "renamed file\n",
+                _ if binary => "binary file changed\n",
+                _ => "file mode changed\n",
+            };
+            hunks.push(Hunk {
+                path,
+                file_header: file_header.to_string(),
+                header: header.to_string(),
+                body: rest.to_string(),
+                position: None,
+            });
+            continue;
+        }
+        
+        for hunk_index in 0..patch.num_hunks() {
+            let (hunk, line_count) = patch.hunk(hunk_index)?;
+            let header = String::from_utf8_lossy(hunk.header()).to_string();
+            let position = Some((hunk.old_start(), hunk.new_start()));
+            
+            let mut body = String::new();
+            for line_index in 0..line_count {
+                let line = patch.line_in_hunk(hunk_index, line_index)?;
+                match line.origin() {
+                    origin @ (' ' | '+' | '-') => {
+                        body.push(origin);
+                        body.push_str(&String::from_utf8_lossy(line.content()));
+                        if !body.ends_with('\n') {
+                            body.push('\n');
+                        }
+                    }
+                    _ => body.push_str("\\ No newline at end of file\n"),
+                }
+            }
+            
+            hunks.push(Hunk { path: path.clone(), file_header: file_header.to_string(), header, body, position });
+        }
+    }
+    
+    Ok(hunks)
+}
+
+// Apply only the given hunks of a diff to a tree
+pub fn apply_hunks_to_tree<'r>(repo: &'r Repository, tree: &Tree, diff: &Diff, hunks: &[&Hunk]) -> Result<Tree<'r>> {
+    let mut options = ApplyOptions::new();
+    filter_hunks(&mut options, hunks);
+    
+    let mut index = repo.apply_to_tree(tree, diff, Some(&mut options))?;
+    let oid = index.write_tree_to(repo)?;
+    Ok(repo.find_tree(oid)?)
+}
+
+// Stage the given hunks, which may have been reduced to some of their lines
+pub fn apply_hunks_to_index(repo: &Repository, hunks: &[Hunk]) -> Result<()> {
+    let mut patch = String::new();
+    let mut previous_path = None;
+    // Lines added minus lines removed by the hunks staged so far in the file
+    let mut offset = 0;
+    
+    for hunk in hunks {
+        if previous_path != Some(&hunk.path) {
+            patch.push_str(&hunk.file_header);
+            previous_path = Some(&hunk.path);
+            offset = 0;
+        }
+        
+        // libgit2 looks for each hunk at its new start line, which has to account for the hunks left out
+        if hunk.position.is_some() {
+            patch.push_str(&hunk.header_with_offset(offset));
+            let (old_count, new_count) = hunk.line_counts();
+            offset += i64::from(new_count) - i64::from(old_count);
+        }
+        patch.push_str(&hunk.body);
+    }
+    
+    let diff = Diff::from_buffer(patch.as_bytes())?;
+    repo.apply(&diff, git2::ApplyLocation::Index, None)?;
+    
+    Ok(())
+}
+
+// Skip every file and hunk but the given ones, the options must not move afterwards as libgit2 keeps a pointer to them
+fn filter_hunks<'a>(options: &mut ApplyOptions<'a>, hunks: &'a [&'a Hunk]) {
+    let current_path = Rc::new(RefCell::new(String::new()));
+    
+    let delta_path = Rc::clone(&current_path);
+    options.delta_callback(move |delta| {
+        let Some(delta) = delta else {
+            return false;
+        };
+        
+        let path = delta.new_file().path()
+            .or_else(|| delta.old_file().path())
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let selected = hunks.iter().any(|h| h.path == path);
+        *delta_path.borrow_mut() = path;
+        selected
+    });
+    
+    options.hunk_callback(move |hunk| {
+        let Some(hunk) = hunk else {
+            return false;
+        };
+        
+        let path = current_path.borrow();
+        hunks.iter().any(|h| {
+            h.path == *path && h.position.is_none_or(|position| position == (hunk.old_start(), hunk.new_start()))
+        })
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // A repository with `file.txt` committed as the lines 1 to 20, and the given content in the working tree
+    fn repository_with_changes(dir: &Path, changed: &str) -> Repository {
+        let repo = Repository::init(dir).unwrap();
+        let original: String = (1..=20).map(|n| format!("{}\n", n)).collect();
+        fs::write(dir.join("file.txt"), original).unwrap();
+        
+        {
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("file.txt")).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+            repo.commit(Some("HEAD"), &signature, &signature, "initial", &tree, &[]).unwrap();
+        }
+        
+        fs::write(dir.join("file.txt"), changed).unwrap();
+        repo
+    }
+    
+    fn staged_content(repo: &Repository) -> String {
+        let index = repo.index().unwrap();
+        let entry = index.get_path(Path::new("file.txt"), 0).unwrap();
+        String::from_utf8(repo.find_blob(entry.id).unwrap().content().to_vec()).unwrap()
+    }
+    
+    // Lines 1 to 20 with `a`, `b` and `c` added on top and line 18 changed
+    fn two_hunk_change() -> String {
+        let lines: String = (1..=20).map(|n| if n == 18 { "eighteen\n".to_string() } else { format!("{}\n", n) }).collect();
+        format!("a\nb\nc\n{}", lines)
+    }
+    
+    #[test]
+    fn stages_a_hunk_after_a_skipped_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = repository_with_changes(dir.path(), &two_hunk_change());
+        
+        let hunks = get_diff_hunks(&get_unstaged_diff(&repo).unwrap()).unwrap();
+        assert_eq!(hunks.len(), 2);
+        
+        apply_hunks_to_index(&repo, &hunks[1..]).unwrap();
+        let expected: String = (1..=20).map(|n| if n == 18 { "eighteen\n".to_string() } else { format!("{}\n", n) }).collect();
+        assert_eq!(staged_content(&repo), expected);
+    }
+    
+    #[test]
+    fn stages_hunks_after_a_partly_selected_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = repository_with_changes(dir.path(), &two_hunk_change());
+        
+        let hunks = get_diff_hunks(&get_unstaged_diff(&repo).unwrap()).unwrap();
+        let selected = vec![hunks[0].with_lines(&[1]), hunks[1].clone()];
+        
+        apply_hunks_to_index(&repo, &selected).unwrap();
+        let expected: String = (1..=20).map(|n| if n == 18 { "eighteen\n".to_string() } else { format!("{}\n", n) }).collect();
+        assert_eq!(staged_content(&repo), format!("b\n{}", expected));
+    }
+    
+    #[test]
+    fn strips_comments_and_blank_lines() {
+        let edited = "\n\nfeat: add parser   \n\n\n# Please enter the commit message\nBody line\n#comment\n\n\n";
+        assert_eq!(strip_commit_message(edited, '#'), "feat: add parser\n\nBody line");
+    }
+    
+    #[test]
+    fn finds_the_default_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init_opts(dir.path(), git2::RepositoryInitOptions::new().initial_head("trunk")).unwrap();
+        
+        let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+        let tree = repo.find_tree(repo.treebuilder(None).unwrap().write().unwrap()).unwrap();
+        let id = repo.commit(Some("HEAD"), &signature, &signature, "initial", &tree, &[]).unwrap();
+        let commit = repo.find_commit(id).unwrap();
+        
+        let mut config = repo.config().unwrap();
+        config.set_str("init.defaultBranch", "develop").unwrap();
+        assert!(get_default_branch(&repo).is_err());
+        
+        repo.branch("master", &commit, false).unwrap();
+        assert_eq!(get_default_branch(&repo).unwrap(), "master");
+        
+        config.set_str("init.defaultBranch", "trunk").unwrap();
+        assert_eq!(get_default_branch(&repo).unwrap(), "trunk");
+        
+        repo.reference("refs/remotes/origin/main", id, false, "test").unwrap();
+        repo.reference_symbolic("refs/remotes/origin/HEAD", "refs/remotes/origin/main", false, "test").unwrap();
+        assert_eq!(get_default_branch(&repo).unwrap(), "origin/main");
+    }
+    
+    #[test]
+    fn uses_the_configured_comment_char() {
+        let edited = "fix: keep #123 references\n; comment\n#not a comment";
+        assert_eq!(strip_commit_message(edited, ';'), "fix: keep #123 references\n#not a comment");
+        assert_eq!(strip_commit_message("# only comments\n", '#'), "");
+    }
+}
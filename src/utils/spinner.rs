@@ -0,0 +1,17 @@
+use std::borrow::Cow;
+use std::time::Duration;
+use indicatif::{ProgressBar, ProgressStyle};
+
+// A ticking spinner shown while waiting on the model or git
+pub fn new_spinner(message: impl Into<Cow<'static, str>>) -> ProgressBar {
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
+            .template("{spinner} {msg}")
+            .unwrap(),
+    );
+    spinner.set_message(message);
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    spinner
+}